@@ -1,43 +1,75 @@
 use std::{
+    cell::Cell,
     cmp::Ordering,
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     env,
-    ffi::OsString,
+    ffi::{OsStr, OsString},
+    fmt::{self, Display},
     fs,
     hash::{Hash, Hasher},
-    io::{self, Write},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    process::{Child, Command as ProcessCommand, Stdio},
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError,
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+    },
     thread,
     time::Duration,
-    time::SystemTime,
+    time::{Instant, SystemTime},
 };
 
 use anyhow::{Context, Result};
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use chrono::{DateTime, Days, Local};
+use clap::{
+    ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+    builder::{PossibleValue, PossibleValuesParser},
+    parser::ValueSource,
+};
+use clap_complete::Shell;
 use crossterm::{
     ExecutableCommand,
-    cursor::MoveTo,
+    cursor::{MoveTo, MoveToColumn},
     terminal::{Clear, ClearType},
+    tty::IsTty,
 };
 use cursive::{
     Cursive,
-    event::{Event, Key},
+    event::{Event, EventResult, EventTrigger, Key, MouseButton, MouseEvent},
     theme::{BaseColor, Color, PaletteColor, Theme},
     traits::{Nameable, Resizable, Scrollable},
+    utils::markup::StyledString,
+    view::SizeConstraint,
     views::{
-        Dialog, DummyView, LinearLayout, Panel, ResizedView, ScrollView, SelectView, TextView,
+        Dialog, DummyView, EditView, LinearLayout, OnEventView, Panel, ResizedView, ScrollView,
+        SelectView, TextView,
     },
 };
-use globset::{Glob, GlobMatcher};
-use owo_colors::OwoColorize;
-use serde::Serialize;
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use globset::{GlobBuilder, GlobMatcher};
+use owo_colors::{AnsiColors, OwoColorize, Style, XtermColors};
+use quick_xml::{
+    Writer,
+    events::{BytesDecl, BytesEnd, BytesStart, Event as XmlEvent},
+};
+use rayon::prelude::*;
+use regex_automata::meta::Regex as MetaRegex;
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+
+macro_rules! outln {
+    () => {
+        write_output(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        write_output(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
 
 #[derive(Parser, Debug)]
 #[command(
     name = "lz",
     version,
-    about = "An advanced ls alternative with interactive browsing.",
-    subcommand_precedence_over_arg = true
+    about = "An advanced ls alternative with interactive browsing."
 )]
 struct Cli {
     #[command(flatten)]
@@ -53,13 +85,171 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Command {
     Interactive(InteractiveArgs),
-    Fastls,
+    TwoPanel(TwoPanelArgs),
+    Fastls(FastlsArgs),
+    Benchmark(BenchArgs),
+    Completions(CompletionsArgs),
+    Cp(CpArgs),
+    Mv(MvArgs),
+    Mkdir(MkdirArgs),
+    Rm(RmArgs),
+    Touch(TouchArgs),
+    Size(SizeArgs),
+    ListRoots,
 }
 
 #[derive(Args, Debug)]
 struct InteractiveArgs {
     #[arg(value_name = "PATH")]
     path: Option<PathBuf>,
+
+    #[arg(long = "interactive-preview-height", value_name = "N")]
+    preview_height: Option<usize>,
+
+    #[arg(long = "interactive-split", value_enum, default_value_t = SplitDirection::Horizontal)]
+    split: SplitDirection,
+
+    #[arg(long = "save-session")]
+    save_session: bool,
+
+    #[arg(long = "restore-session")]
+    restore_session: bool,
+
+    #[arg(long = "interactive-mouse")]
+    mouse: bool,
+
+    #[arg(long = "interactive-status-bar")]
+    status_bar: bool,
+
+    #[arg(
+        long = "interactive-grep",
+        visible_alias = "grep",
+        value_name = "PATTERN"
+    )]
+    initial_filter: Option<String>,
+
+    #[arg(long = "interactive-cmd", visible_alias = "cmd", value_name = "CMD")]
+    cmd: Option<String>,
+
+    #[arg(long = "interactive-on-exit-cd", visible_alias = "on-exit-cd")]
+    on_exit_cd: bool,
+
+    #[arg(long = "interactive-refresh-interval", value_name = "SECS")]
+    refresh_interval: Option<f64>,
+
+    #[arg(long = "interactive-no-panel")]
+    no_panel: bool,
+}
+
+#[derive(Args, Debug)]
+struct TwoPanelArgs {
+    #[arg(value_name = "LEFT")]
+    left: Option<PathBuf>,
+
+    #[arg(value_name = "RIGHT")]
+    right: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct FastlsArgs {
+    #[arg(long = "mode", value_enum, default_value_t = FastlsMode::Folder)]
+    mode: FastlsMode,
+}
+
+#[derive(Args, Debug)]
+struct BenchArgs {
+    #[arg(short = 'n', long = "runs", default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    n: u64,
+
+    #[arg(value_name = "PATH")]
+    path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct CpArgs {
+    #[arg(value_name = "SRC")]
+    src: PathBuf,
+
+    #[arg(value_name = "DST")]
+    dst: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct MvArgs {
+    #[arg(value_name = "SRC")]
+    src: PathBuf,
+
+    #[arg(value_name = "DST")]
+    dst: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct MkdirArgs {
+    #[arg(value_name = "PATH")]
+    path: PathBuf,
+
+    #[arg(short = 'p', long = "parents")]
+    parents: bool,
+
+    #[arg(long = "list")]
+    list: bool,
+}
+
+#[derive(Args, Debug)]
+struct RmArgs {
+    #[arg(value_name = "PATH")]
+    path: PathBuf,
+
+    #[arg(short = 'R', long = "recursive")]
+    recursive: bool,
+
+    #[arg(long = "trash")]
+    trash: bool,
+
+    #[arg(short = 'f', long = "force")]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+struct TouchArgs {
+    #[arg(value_name = "FILE")]
+    path: PathBuf,
+
+    #[arg(short = 'c', long = "no-create")]
+    no_create: bool,
+
+    #[arg(short = 'd', long = "timestamp", value_name = "TIME")]
+    timestamp: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct SizeArgs {
+    #[arg(value_name = "PATH")]
+    path: Option<PathBuf>,
+
+    #[arg(short = 'R', long = "recursive")]
+    recursive: bool,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    #[arg(value_name = "SHELL", value_enum)]
+    shell: Shell,
+
+    #[arg(long = "cd-alias")]
+    cd_alias: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum FastlsMode {
+    Folder,
+    File,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -70,47 +260,357 @@ struct ListOptions {
     #[arg(global = true, short = 'l', long = "long")]
     long: bool,
 
+    #[arg(global = true, long = "preview", value_name = "N", requires = "long")]
+    preview: Option<usize>,
+
+    #[arg(global = true, long = "wide")]
+    wide: bool,
+
+    #[arg(global = true, long = "print-dir")]
+    print_dir: bool,
+
+    #[arg(global = true, long = "no-newline")]
+    no_newline: bool,
+
+    #[arg(
+        global = true,
+        long = "header-gap",
+        value_name = "N",
+        default_value_t = 0
+    )]
+    header_gap: usize,
+
+    #[arg(global = true, long = "group-by-ext", conflicts_with = "tree")]
+    group_by_ext: bool,
+
+    #[arg(global = true, long = "group-by-date", conflicts_with_all = ["tree", "group_by_ext"])]
+    group_by_date: bool,
+
     #[arg(global = true, long = "icons")]
     icons: bool,
 
+    #[arg(
+        global = true,
+        long = "icons-padding",
+        value_name = "N",
+        default_value_t = 1,
+        requires = "icons"
+    )]
+    icons_padding: usize,
+
+    #[arg(global = true, long = "no-icons-for-symlinks", requires = "icons")]
+    no_symlink_icon: bool,
+
+    #[arg(global = true, long = "include-parent")]
+    include_parent: bool,
+
+    #[arg(
+        global = true,
+        long = "emoji-width",
+        value_name = "1|2",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(1..=2)
+    )]
+    emoji_width: u8,
+
     #[arg(global = true, long = "tree")]
     tree: bool,
 
+    #[arg(global = true, long = "compact")]
+    compact: bool,
+
+    #[arg(global = true, long = "reverse-tree", requires = "tree")]
+    reverse_tree: bool,
+
+    #[arg(global = true, long = "show-count", requires = "tree")]
+    show_count: bool,
+
+    #[arg(global = true, long = "subtree-sizes", requires = "tree")]
+    subtree_sizes: bool,
+
+    #[arg(global = true, long = "sort-dirs-by-size", requires = "du")]
+    sort_dirs_by_size: bool,
+
     #[arg(global = true, long = "rainbow")]
     rainbow: bool,
 
+    #[arg(global = true, long = "color-theme-256")]
+    color_256: bool,
+
+    #[arg(global = true, long = "strip-ansi")]
+    strip_ansi: bool,
+
+    #[arg(global = true, long = "highlight-pattern", value_name = "PATTERN")]
+    highlight_pattern: Option<String>,
+
+    #[arg(global = true, long = "plain")]
+    plain: bool,
+
+    #[arg(global = true, long = "color", value_name = "WHEN", value_enum, default_value_t = ColorWhen::Auto)]
+    color: ColorWhen,
+
+    #[arg(skip)]
+    colors: ColorConfig,
+
     #[arg(global = true, long = "filter", value_name = "PATTERN")]
     filter: Option<String>,
 
+    #[arg(
+        global = true,
+        long = "search",
+        value_name = "TERM",
+        conflicts_with = "filter"
+    )]
+    search: Option<String>,
+
+    #[arg(global = true, long = "fuzzy", requires = "filter")]
+    fuzzy: bool,
+
+    #[arg(
+        global = true,
+        long = "fuzzy-threshold",
+        value_name = "SCORE",
+        default_value_t = 0,
+        requires = "fuzzy"
+    )]
+    fuzzy_threshold: i64,
+
+    #[arg(global = true, long = "interactive-fuzzy")]
+    interactive_fuzzy: bool,
+
+    #[arg(global = true, long = "bat-integration")]
+    bat_integration: bool,
+
+    #[arg(global = true, long = "preview-cmd", value_name = "CMD")]
+    preview_cmd: Option<String>,
+
+    #[arg(global = true, long = "delta-integration")]
+    delta_integration: bool,
+
+    #[arg(global = true, long = "tmux-integration")]
+    tmux_integration: bool,
+
+    #[arg(global = true, long = "git-branch")]
+    git_branch: bool,
+
+    #[arg(global = true, long = "git-log", value_name = "N")]
+    git_log: Option<usize>,
+
+    #[arg(global = true, long = "version-check")]
+    version_check: bool,
+
+    #[arg(global = true, long = "no-network")]
+    no_network: bool,
+
+    #[arg(global = true, long = "remote", value_name = "USER@HOST:PATH")]
+    remote: Option<String>,
+
+    #[arg(global = true, long = "remote-accept-new-host", requires = "remote")]
+    remote_accept_new_host: bool,
+
+    #[arg(
+        global = true,
+        long = "s3",
+        value_name = "S3_URI",
+        conflicts_with = "remote"
+    )]
+    s3_uri: Option<String>,
+
+    #[arg(
+        global = true,
+        long = "s3-region",
+        value_name = "REGION",
+        requires = "s3_uri"
+    )]
+    s3_region: Option<String>,
+
+    #[arg(
+        global = true,
+        long = "s3-endpoint",
+        value_name = "URL",
+        requires = "s3_uri"
+    )]
+    s3_endpoint: Option<String>,
+
+    #[arg(global = true, short = 'i', long = "ignore-case")]
+    ignore_case: bool,
+
     #[arg(global = true, long = "only-dirs")]
     only_dirs: bool,
 
     #[arg(global = true, long = "only-files")]
     only_files: bool,
 
+    #[arg(global = true, long = "dirsonly-deep", conflicts_with = "only_files")]
+    dirsonly_deep: bool,
+
+    #[arg(global = true, long = "show-hidden-only")]
+    hidden_only: bool,
+
+    #[arg(global = true, long = "hidden-count")]
+    hidden_count: bool,
+
     #[arg(global = true, long = "json")]
     json: bool,
 
+    #[arg(global = true, long = "xml", conflicts_with = "json")]
+    xml: bool,
+
+    #[arg(global = true, long = "toml", conflicts_with_all = ["json", "xml"])]
+    toml_output: bool,
+
+    #[arg(global = true, long = "yaml", conflicts_with_all = ["json", "xml", "toml_output"])]
+    yaml_output: bool,
+
+    #[arg(global = true, long = "yaml-flow", requires = "yaml_output")]
+    yaml_flow: bool,
+
+    #[arg(global = true, long = "tree-json", conflicts_with = "reverse_tree")]
+    tree_json: bool,
+
+    #[arg(global = true, long = "json-stream", requires = "json")]
+    json_stream: bool,
+
     #[arg(global = true, long = "du")]
     du: bool,
 
+    #[arg(global = true, long = "real-time-summary", requires = "du")]
+    real_time_summary: bool,
+
+    #[arg(global = true, long = "no-progress")]
+    no_progress: bool,
+
+    #[arg(global = true, long = "log", value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long = "log-max-size",
+        value_name = "SIZE",
+        default_value = "10M",
+        value_parser = parse_byte_size,
+        requires = "log_file"
+    )]
+    log_max_size: u64,
+
+    #[arg(global = true, long = "error-output", value_name = "FILE")]
+    error_output: Option<PathBuf>,
+
+    #[arg(global = true, long = "concurrent-summary")]
+    concurrent_summary: bool,
+
+    #[arg(
+        global = true,
+        long = "concurrent-limit",
+        value_name = "N",
+        requires = "concurrent_summary",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    concurrent_limit: Option<usize>,
+
     #[arg(global = true, long = "extensions")]
     extensions: bool,
 
+    #[arg(global = true, long = "summary-format", value_enum, default_value_t = SummaryFormat::Table)]
+    summary_format: SummaryFormat,
+
+    #[arg(global = true, long = "ext-chart", requires = "extensions")]
+    ext_chart: bool,
+
+    #[arg(
+        global = true,
+        long = "chart-width",
+        value_name = "N",
+        default_value_t = 40
+    )]
+    chart_width: usize,
+
     #[arg(global = true, long = "watch")]
     watch: bool,
 
+    #[arg(global = true, long = "watch-diff", requires = "watch")]
+    watch_diff: bool,
+
+    #[arg(
+        global = true,
+        long = "watch-events",
+        requires = "watch",
+        conflicts_with = "watch_diff"
+    )]
+    watch_events: bool,
+
     #[arg(global = true, long = "human")]
     human: bool,
 
+    #[arg(
+        global = true,
+        long = "timestamp-utc",
+        conflicts_with = "timestamp_local"
+    )]
+    timestamp_utc: bool,
+
+    #[arg(
+        global = true,
+        long = "timestamp-precision",
+        value_enum,
+        value_name = "PRECISION"
+    )]
+    timestamp_precision: Option<TimePrecision>,
+
+    #[arg(global = true, long = "timestamp-local")]
+    timestamp_local: bool,
+
     #[arg(global = true, long = "sort", value_enum, default_value_t = SortKey::Name)]
     sort: SortKey,
 
     #[arg(global = true, short = 'r', long = "reverse")]
     reverse: bool,
+
+    #[arg(global = true, long = "no-sort", conflicts_with_all = ["sort", "reverse"])]
+    no_sort: bool,
+
+    #[arg(global = true, long = "entries-first", conflicts_with = "no_sort")]
+    entries_first: bool,
+
+    #[arg(global = true, long = "profile")]
+    profile: bool,
+
+    #[arg(global = true, long = "print-cmd")]
+    print_cmd: bool,
+
+    #[arg(global = true, long = "profile-memory")]
+    profile_memory: bool,
+
+    #[arg(global = true, long = "dry-run")]
+    dry_run: bool,
+
+    #[arg(global = true, long = "export-env")]
+    export_env: bool,
+
+    #[arg(global = true, long = "pager", conflicts_with = "auto_pager")]
+    pager: bool,
+
+    #[arg(global = true, long = "auto-pager")]
+    auto_pager: bool,
+
+    #[arg(global = true, long = "sniff")]
+    sniff: bool,
+
+    #[arg(global = true, long = "mount-info", requires = "long")]
+    mount_info: bool,
+
+    #[arg(global = true, long = "hardlink-info")]
+    hardlink_info: bool,
+
+    #[arg(global = true, long = "sparse")]
+    sparse: bool,
+
+    #[arg(global = true, long = "author", value_name = "PATTERN")]
+    author: Option<String>,
 }
 
-#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum SortKey {
     Name,
     Size,
@@ -118,6 +618,27 @@ enum SortKey {
     Age,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum TimePrecision {
+    Seconds,
+    Millis,
+    Nanos,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorWhen {
+    Always,
+    Auto,
+    Never,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SummaryFormat {
+    Table,
+    Inline,
+    None,
+}
+
 #[derive(Debug, Clone)]
 struct EntryInfo {
     name: OsString,
@@ -127,7 +648,45 @@ struct EntryInfo {
     modified: Option<SystemTime>,
 }
 
-impl EntryInfo {
+// What filtering, sorting and name formatting need from an entry, so local entries and remote
+// (`--remote`, `--s3`) entries share that code.
+trait ListingEntry {
+    fn name(&self) -> &OsStr;
+    fn path(&self) -> &Path;
+    fn modified(&self) -> Option<SystemTime>;
+    fn is_dir(&self) -> bool;
+    fn is_symlink(&self) -> bool;
+    fn is_readonly(&self) -> bool;
+    fn size(&self) -> u64;
+
+    fn content_type(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn kind(&self) -> &'static str {
+        if self.is_dir() {
+            "dir"
+        } else if self.is_symlink() {
+            "symlink"
+        } else {
+            "file"
+        }
+    }
+}
+
+impl ListingEntry for EntryInfo {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
     fn is_dir(&self) -> bool {
         self.file_type.is_dir()
     }
@@ -136,6 +695,10 @@ impl EntryInfo {
         self.file_type.is_symlink()
     }
 
+    fn is_readonly(&self) -> bool {
+        self.metadata.permissions().readonly()
+    }
+
     fn size(&self) -> u64 {
         if self.file_type.is_file() {
             self.metadata.len()
@@ -143,25 +706,267 @@ impl EntryInfo {
             0
         }
     }
+
+    fn content_type(&self) -> Option<&'static str> {
+        if !self.file_type.is_file() || self.path.extension().is_some() {
+            return None;
+        }
+        sniff_magic(&self.path)
+    }
+}
+
+// `fs::Metadata` can't be rebuilt from data, so entries only serialize; names and paths go through
+// `OsStr` so non-UTF-8 filenames survive.
+impl Serialize for EntryInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("EntryInfo", 5)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("path", self.path.as_os_str())?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("size", &self.size())?;
+        state.serialize_field("modified", &self.modified)?;
+        state.end()
+    }
+}
+
+fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    path.as_os_str().serialize(serializer)
+}
+
+const MAGIC_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "png"),
+    (b"\xff\xd8\xff", "jpeg"),
+    (b"GIF87a", "gif"),
+    (b"GIF89a", "gif"),
+    (b"%PDF-", "pdf"),
+    (b"\x7fELF", "elf"),
+    (b"MZ", "pe"),
+    (b"\x00asm", "wasm"),
+    (b"PK\x03\x04", "zip"),
+    (b"\x1f\x8b", "gzip"),
+    (b"BZh", "bzip2"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"7z\xbc\xaf\x27\x1c", "7z"),
+    (b"SQLite format 3\x00", "sqlite"),
+    (b"#!", "script"),
+];
+
+fn sniff_magic(path: &Path) -> Option<&'static str> {
+    let mut buf = [0u8; 16];
+    let mut file = fs::File::open(path).ok()?;
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return None,
+        }
+    }
+    let head = &buf[..len];
+    MAGIC_SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|(_, kind)| *kind)
+}
+
+fn content_type_icon(kind: &str) -> &'static str {
+    match kind {
+        "png" | "jpeg" | "gif" => "🎨",
+        "pdf" => "📕",
+        "elf" | "pe" | "wasm" => "🔧",
+        "zip" | "gzip" | "bzip2" | "xz" | "7z" => "📦",
+        "sqlite" => "💾",
+        "script" => "📜",
+        _ => "📄",
+    }
+}
+
+// Jemalloc only reports current usage, so the wrapper keeps its own high-water mark.
+#[cfg(feature = "memory-profile")]
+struct PeakAlloc;
+
+#[cfg(feature = "memory-profile")]
+static HEAP_CURRENT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+#[cfg(feature = "memory-profile")]
+static HEAP_PEAK: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(feature = "memory-profile")]
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+#[cfg(feature = "memory-profile")]
+unsafe impl std::alloc::GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let ptr = unsafe { tikv_jemallocator::Jemalloc.alloc(layout) };
+        if !ptr.is_null() {
+            let current = HEAP_CURRENT.fetch_add(layout.size(), AtomicOrdering::Relaxed);
+            HEAP_PEAK.fetch_max(current + layout.size(), AtomicOrdering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { tikv_jemallocator::Jemalloc.dealloc(ptr, layout) };
+        HEAP_CURRENT.fetch_sub(layout.size(), AtomicOrdering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { tikv_jemallocator::Jemalloc.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            if new_size >= layout.size() {
+                let grow = new_size - layout.size();
+                let current = HEAP_CURRENT.fetch_add(grow, AtomicOrdering::Relaxed);
+                HEAP_PEAK.fetch_max(current + grow, AtomicOrdering::Relaxed);
+            } else {
+                HEAP_CURRENT.fetch_sub(layout.size() - new_size, AtomicOrdering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(feature = "memory-profile")]
+fn print_memory_stats() {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let peak = HEAP_PEAK.load(AtomicOrdering::Relaxed) as f64 / MIB;
+    eprintln!("Peak heap: {peak:.1} MiB");
+    // The stats are cached per epoch; advancing refreshes them.
+    if tikv_jemalloc_ctl::epoch::advance().is_ok()
+        && let (Ok(allocated), Ok(resident)) = (
+            tikv_jemalloc_ctl::stats::allocated::read(),
+            tikv_jemalloc_ctl::stats::resident::read(),
+        )
+    {
+        eprintln!(
+            "Heap now: {:.1} MiB allocated, {:.1} MiB resident",
+            allocated as f64 / MIB,
+            resident as f64 / MIB
+        );
+    }
 }
 
+#[cfg(not(feature = "memory-profile"))]
+fn print_memory_stats() {}
+
 fn main() {
     if let Err(err) = run() {
-        eprintln!("{}", format!("{err:#}").bright_red());
+        report_error(&err);
         std::process::exit(1);
     }
 }
 
 fn run() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let color = match cli.options.color {
+        ColorWhen::Always => true,
+        ColorWhen::Never => false,
+        ColorWhen::Auto => io::stdout().is_tty() && env::var_os("NO_COLOR").is_none(),
+    };
+    COLOR_ENABLED.store(color && !cli.options.plain, AtomicOrdering::Relaxed);
+    COLOR_256.store(cli.options.color_256, AtomicOrdering::Relaxed);
+    cli.options.colors = ColorConfig::load()?;
+    normalize_options(&mut cli.options);
+
+    if cli.options.profile_memory && !cfg!(feature = "memory-profile") {
+        anyhow::bail!("lz was built without the `memory-profile` feature");
+    }
+    highlight_regex(&cli.options)?;
+    if let Some(path) = &cli.options.error_output {
+        let file = fs::File::create(path)
+            .with_context(|| format!("Failed to create error output {}", path.display()))?;
+        *ERROR_OUTPUT.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+    }
+    if let Some(path) = &cli.options.log_file {
+        open_log(path, cli.options.log_max_size)?;
+    }
+    if cli.options.version_check {
+        return run_version_check(&cli.options);
+    }
+    if cli.options.print_cmd && cli.command.is_none() {
+        print_effective_command(
+            &matches,
+            &cli.options,
+            cli.path.as_deref().unwrap_or(Path::new(".")),
+        );
+    }
+
+    output().no_newline = cli.options.no_newline;
+    output().strip_ansi = cli.options.strip_ansi;
+    let pageable = matches!(
+        cli.command,
+        None | Some(Command::Fastls(_) | Command::Benchmark(_))
+    );
+    if pageable && !cli.options.json && !cli.options.watch && io::stdout().is_tty() {
+        if cli.options.pager {
+            set_output(OutputSink::Pager(spawn_pager()?));
+        } else if cli.options.auto_pager {
+            set_output(OutputSink::Buffer(Vec::new()));
+        }
+    }
+
+    let result = run_command(cli);
+    finish_output()?;
+    result
+}
 
+fn run_command(cli: Cli) -> Result<()> {
     match cli.command {
         Some(Command::Interactive(args)) => {
-            let start = args.path.unwrap_or_else(|| PathBuf::from("."));
-            run_interactive(start, cli.options)?;
+            run_interactive(args, cli.options)?;
         }
-        Some(Command::Fastls) => {
-            run_fastls(cli.options)?;
+        Some(Command::TwoPanel(args)) => {
+            let left = args.left.unwrap_or_else(|| PathBuf::from("."));
+            let right = args.right.unwrap_or_else(|| left.clone());
+            run_two_panel(left, right, cli.options)?;
+        }
+        Some(Command::Fastls(args)) => {
+            run_fastls(args.mode, cli.options)?;
+        }
+        Some(Command::Cp(args)) => {
+            run_cp(&args.src, &args.dst, &cli.options)?;
+            log_operation("cp", &args.src, None);
+        }
+        Some(Command::Mv(args)) => {
+            run_mv(&args.src, &args.dst, &cli.options)?;
+            log_operation("mv", &args.src, None);
+        }
+        Some(Command::Mkdir(args)) => {
+            run_mkdir(&args, &cli.options)?;
+            log_operation("mkdir", &args.path, None);
+        }
+        Some(Command::Rm(args)) => {
+            run_rm(&args)?;
+            log_operation("rm", &args.path, None);
+        }
+        Some(Command::Touch(args)) => {
+            run_touch(&args)?;
+            log_operation("touch", &args.path, None);
+        }
+        Some(Command::Size(args)) => {
+            let path = args.path.unwrap_or_else(|| PathBuf::from("."));
+            run_size(&path, args.recursive, &cli.options)?;
+        }
+        Some(Command::ListRoots) => {
+            run_list_roots(&cli.options);
+        }
+        Some(Command::Completions(args)) if args.cd_alias => {
+            print_cd_alias(args.shell)?;
+        }
+        Some(Command::Completions(args)) => {
+            print_completions(args.shell);
+        }
+        Some(Command::Benchmark(args)) => {
+            let path = args.path.unwrap_or_else(|| PathBuf::from("."));
+            run_benchmark(&path, args.n, &cli.options)?;
+        }
+        None if cli.options.s3_uri.is_some() => {
+            let uri = cli.options.s3_uri.as_deref().unwrap_or_default();
+            run_s3_listing(uri, &cli.options)?;
+        }
+        None if cli.options.remote.is_some() => {
+            let target = cli.options.remote.as_deref().unwrap_or_default();
+            run_remote_listing(target, &cli.options)?;
         }
         None => {
             let path = cli.path.unwrap_or_else(|| PathBuf::from("."));
@@ -172,509 +977,3247 @@ fn run() -> Result<()> {
     Ok(())
 }
 
-fn run_fastls(options: ListOptions) -> Result<()> {
-    let picked = rfd::FileDialog::new()
-        .set_title("Choose a folder to list")
-        .pick_folder();
-    if let Some(dir) = picked {
-        list_path(&dir, &options)?;
+// Options given on the command line or through the environment, in definition order, plus the
+// config file when one is loaded.
+// Shorthand flags expand into the options they stand for.
+fn normalize_options(options: &mut ListOptions) {
+    if options.dirsonly_deep {
+        options.tree = true;
+        options.only_dirs = true;
+    }
+    if options.tree_json {
+        options.tree = true;
+        options.json = true;
+    }
+    if let Some(term) = options.search.take() {
+        options.filter = Some(format!("*{term}*"));
+        options.highlight_pattern.get_or_insert(term);
+    }
+    if options.no_progress {
+        options.real_time_summary = false;
     }
-    Ok(())
 }
 
-fn list_path(path: &Path, options: &ListOptions) -> Result<()> {
-    if options.watch {
-        loop {
-            if !options.json {
-                let mut stdout = io::stdout();
-                stdout.execute(Clear(ClearType::All))?;
-                stdout.execute(MoveTo(0, 0))?;
-            }
-
-            if let Err(err) = list_path_once(path, options) {
-                if options.json {
-                    let out = JsonOutput {
-                        root: path.display().to_string(),
-                        entries: Vec::new(),
-                        summary: None,
-                        error: Some(format!("{err:#}")),
-                    };
-                    println!("{}", serde_json::to_string(&out)?);
-                } else {
-                    eprintln!("{}", format!("{err:#}").bright_red());
-                }
-            }
-
-            if options.json {
-                io::stdout().flush()?;
-            }
-
-            thread::sleep(Duration::from_secs(2));
-        }
-    } else {
-        list_path_once(path, options)
+fn print_effective_command(matches: &ArgMatches, options: &ListOptions, path: &Path) {
+    let quote = |args: Vec<String>| {
+        args.iter()
+            .map(|arg| format!(" {}", shell_quote(arg)))
+            .collect::<String>()
+    };
+    let (args, defaults) = effective_args(matches, options);
+    eprintln!(
+        "# lz list {}{}",
+        shell_quote(&path.to_string_lossy()),
+        quote(args)
+    );
+    if !defaults.is_empty() {
+        eprintln!("# defaults:{}", quote(defaults));
+    }
+    if let Some(path) = config_dir()
+        .map(|dir| dir.join("colors.toml"))
+        .filter(|path| path.is_file())
+    {
+        eprintln!("# config: {}", path.display());
     }
 }
 
-fn list_path_once(path: &Path, options: &ListOptions) -> Result<()> {
-    let matcher = compile_filter(options)?;
-    let metadata = fs::symlink_metadata(path)
-        .with_context(|| format!("Failed to read metadata for {}", path.display()))?;
+// Returns the options that are in effect as argv, split into those set on the command line, in
+// the environment or by the rewrites in `run` (taken from `options`), and the built-in defaults.
+// The defaults are kept apart because some only parse next to the flag they require.
+fn effective_args(matches: &ArgMatches, options: &ListOptions) -> (Vec<String>, Vec<String>) {
+    let flag = |set: bool| set.then(Vec::new);
+    let value = |value: &Option<String>| value.clone().map(|v| vec![v]);
+    let rewritten: HashMap<&str, Option<Vec<String>>> = HashMap::from([
+        ("tree", flag(options.tree)),
+        ("only_dirs", flag(options.only_dirs)),
+        ("json", flag(options.json)),
+        ("real_time_summary", flag(options.real_time_summary)),
+        ("search", None),
+        ("filter", value(&options.filter)),
+        ("highlight_pattern", value(&options.highlight_pattern)),
+    ]);
+
+    let (mut args, mut defaults) = (Vec::new(), Vec::new());
+    let cmd = Cli::command();
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        let is_default = matches.value_source(id) == Some(ValueSource::DefaultValue);
+        let values = match rewritten.get(id) {
+            Some(values) => values.clone(),
+            None if arg.get_action().takes_values() => matches
+                .get_raw(id)
+                .map(|raw| raw.map(|v| v.to_string_lossy().into_owned()).collect()),
+            None => (!is_default && matches.value_source(id).is_some()).then(Vec::new),
+        };
+        let Some(values) = values else {
+            continue;
+        };
+        let target = if is_default && !rewritten.contains_key(id) {
+            &mut defaults
+        } else {
+            &mut args
+        };
+        if values.is_empty() {
+            target.push(format!("--{long}"));
+        }
+        for value in values {
+            target.push(format!("--{long}"));
+            target.push(value);
+        }
+    }
+    (args, defaults)
+}
 
-    let summary = if options.du || options.extensions {
-        Some(compute_summary(path, options, matcher.as_ref())?)
+fn shell_quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        value.to_string()
     } else {
-        None
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+fn print_completions(shell: Shell) {
+    // Aliases are hidden from generated completions, so list them as regular values here (the
+    // actual parser still resolves them through `SortKey`).
+    let sort_values: Vec<PossibleValue> = SortKey::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .flat_map(|value| {
+            value
+                .get_name_and_aliases()
+                .map(|name| PossibleValue::new(name.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let mut cmd = Cli::command().mut_arg("sort", |arg| {
+        arg.value_parser(PossibleValuesParser::new(sort_values))
+    });
+    clap_complete::generate(shell, &mut cmd, "lz", &mut io::stdout());
+}
+
+fn print_cd_alias(shell: Shell) -> Result<()> {
+    let alias = match shell {
+        Shell::Bash | Shell::Zsh => {
+            "lzcd() {\n    eval \"$(command lz interactive --on-exit-cd \"$@\")\"\n}"
+        }
+        Shell::Fish => "function lzcd\n    eval (command lz interactive --on-exit-cd $argv)\nend",
+        Shell::PowerShell => {
+            "function lzcd {\n    Invoke-Expression (& lz interactive --on-exit-cd @args | Out-String)\n}"
+        }
+        _ => anyhow::bail!("--cd-alias is not supported for {shell}"),
     };
+    println!("{alias}");
+    Ok(())
+}
 
-    if metadata.is_dir() {
-        let entries = build_display_entries_for_dir(path, path, options, matcher.as_ref())?;
-        output_entries(path, &entries, summary.as_ref(), options)
-    } else {
-        let file_type = metadata.file_type();
-        let name = path
-            .file_name()
-            .map(OsString::from)
-            .unwrap_or_else(|| OsString::from(path.as_os_str()));
-        let entry = EntryInfo {
-            name,
-            path: path.to_path_buf(),
-            file_type,
-            modified: metadata.modified().ok(),
-            metadata,
-        };
-        let rel_path = path
-            .file_name()
-            .map(PathBuf::from)
-            .unwrap_or_else(|| path.to_path_buf());
-        let display = DisplayEntry {
-            entry,
-            prefix: String::new(),
-            rel_path,
-        };
-        output_entries(path, &[display], summary.as_ref(), options)
+// An entry from a remote (SFTP or S3) listing. There is no local `fs::Metadata` behind it, so it
+// only goes through the parts of the pipeline that work on `ListingEntry`.
+#[derive(Debug)]
+struct RemoteEntry {
+    name: String,
+    kind: &'static str,
+    size: u64,
+    modified: Option<SystemTime>,
+    readonly: bool,
+}
+
+impl ListingEntry for RemoteEntry {
+    fn name(&self) -> &OsStr {
+        OsStr::new(&self.name)
+    }
+
+    fn path(&self) -> &Path {
+        Path::new(&self.name)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    fn is_dir(&self) -> bool {
+        self.kind == "dir"
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.kind == "symlink"
+    }
+
+    fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    fn size(&self) -> u64 {
+        self.size
     }
 }
 
-fn output_entries(
-    root: &Path,
-    entries: &[DisplayEntry],
-    summary: Option<&ListingSummary>,
+impl RemoteEntry {
+    fn to_json(&self) -> JsonEntry {
+        JsonEntry {
+            rel_path: self.name.clone(),
+            name: self.name.clone(),
+            kind: self.kind.to_string(),
+            size: self.size,
+            modified: self
+                .modified
+                .map(|time| humantime::format_rfc3339(time).to_string()),
+            depth: 0,
+            entry_count: None,
+            subtree_bytes: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+// Flags that need a local walk or local metadata; a remote listing refuses them instead of
+// silently printing something else.
+fn reject_remote_options(options: &ListOptions, source: &str) -> Result<()> {
+    let unsupported = [
+        (
+            options.tree || options.compact || options.reverse_tree,
+            "--tree",
+        ),
+        (options.dirsonly_deep, "--dirsonly-deep"),
+        (options.tree_json, "--tree-json"),
+        (options.sort_dirs_by_size, "--sort-dirs-by-size"),
+        (options.include_parent, "--include-parent"),
+        (options.du, "--du"),
+        (options.extensions || options.ext_chart, "--extensions"),
+        (options.group_by_ext, "--group-by-ext"),
+        (options.group_by_date, "--group-by-date"),
+        (options.preview.is_some(), "--preview"),
+        (options.sniff, "--sniff"),
+        (options.mount_info, "--mount-info"),
+        (options.hardlink_info, "--hardlink-info"),
+        (options.sparse, "--sparse"),
+        (options.author.is_some(), "--author"),
+        (options.watch, "--watch"),
+        (options.hidden_count, "--hidden-count"),
+        (options.export_env, "--export-env"),
+        (options.dry_run, "--dry-run"),
+    ];
+    if let Some((_, flag)) = unsupported.iter().find(|(set, _)| *set) {
+        anyhow::bail!("{flag} is not supported with {source}");
+    }
+    Ok(())
+}
+
+fn filter_remote_entries(
+    mut entries: Vec<RemoteEntry>,
+    options: &ListOptions,
+) -> Result<Vec<RemoteEntry>> {
+    let matcher = compile_filter(options)?;
+    entries.retain(|entry| {
+        (options.all || options.hidden_only || !is_hidden(entry.name()))
+            && should_print_entry(entry, entry.path(), options, matcher.as_ref())
+    });
+    sort_listing(&mut entries, options, None);
+    Ok(entries)
+}
+
+fn output_remote_entries(
+    root: &str,
+    entries: Vec<RemoteEntry>,
     options: &ListOptions,
 ) -> Result<()> {
-    if options.json {
+    let entries = filter_remote_entries(entries, options)?;
+
+    if options.json || options.xml || options.toml_output || options.yaml_output {
         let out = JsonOutput {
-            root: root.display().to_string(),
-            entries: entries.iter().map(|e| e.to_json()).collect(),
-            summary: summary.map(|s| s.to_json(options.extensions)),
+            version: JSON_FORMAT_VERSION,
+            refresh_id: None,
+            root: root.to_string(),
+            entries: entries.iter().map(RemoteEntry::to_json).collect(),
+            summary: None,
             error: None,
         };
-        println!("{}", serde_json::to_string_pretty(&out)?);
+        return output_structured(&out, options);
+    }
+
+    if options.print_dir {
+        outln!("{}", paint(format!("{root}:"), Style::new().bright_blue()));
+        print_header_gap(options);
+    }
+
+    if options.wide {
+        print_wide_rows(
+            entries.iter().map(|entry| {
+                let name = format_name(entry, entry.path(), options);
+                (name, entry.size, entry.modified)
+            }),
+            options,
+        );
         return Ok(());
     }
 
-    if options.long {
-        print_long(entries, options)?;
-    } else {
-        for entry in entries {
-            let prefix = if entry.prefix.is_empty() {
-                String::new()
-            } else {
-                format!("{}", entry.prefix.bright_black())
-            };
-            println!(
-                "{prefix}{}",
-                format_name(&entry.entry, &entry.rel_path, options)
+    let sizes: Vec<String> = entries
+        .iter()
+        .map(|entry| format_size(entry.size, options.human))
+        .collect();
+    let size_w = sizes
+        .iter()
+        .map(|size| size.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (entry, size) in entries.iter().zip(sizes) {
+        let name = format_name(entry, entry.path(), options);
+        if options.long {
+            let time = entry
+                .modified
+                .map(|t| format_timestamp(t, options, true))
+                .unwrap_or_else(|| "-".to_string());
+            outln!(
+                "{}  {}  {}  {name}",
+                format_mode(entry),
+                paint(format!("{size:>size_w$}"), Style::new().bright_magenta()),
+                paint(time, Style::new().bright_black())
             );
+        } else {
+            outln!("{name}");
         }
     }
+    Ok(())
+}
 
-    if let Some(summary) = summary {
-        if options.du {
-            println!(
-                "{} {}",
-                "Total:".bright_yellow(),
-                format_size(summary.total_bytes, true).bright_yellow()
-            );
+#[derive(Debug, PartialEq)]
+struct SshTarget {
+    user: String,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl SshTarget {
+    // `[user@]host[:port][:path]`; the user defaults to $USER, the port to 22 and the path to the
+    // login directory. A purely numeric segment right after the host is taken as the port.
+    fn parse(target: &str) -> Result<Self> {
+        let (user, rest) = match target.split_once('@') {
+            Some((user, rest)) => (user.to_string(), rest),
+            None => (
+                env::var("USER").context("No user in --remote and $USER is unset")?,
+                target,
+            ),
+        };
+        let (host, rest) = rest.split_once(':').unwrap_or((rest, ""));
+        if host.is_empty() {
+            anyhow::bail!("Invalid --remote target: {target}");
         }
-        if options.extensions {
-            for (ext, s) in &summary.ext {
-                let ext_label = if ext.is_empty() {
-                    "(none)".to_string()
-                } else {
-                    format!(".{ext}")
-                };
-                let files = format!("{} files", s.files);
-                let bytes = format_size(s.bytes, true);
-                println!(
-                    "{}  {}  {}",
-                    ext_label.bright_blue(),
-                    files.bright_white(),
-                    bytes.bright_magenta()
-                );
-            }
+        let (port, path) = match rest.split_once(':').unwrap_or((rest, "")) {
+            (port, path) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => (
+                port.parse()
+                    .with_context(|| format!("Invalid port in --remote target: {target}"))?,
+                path,
+            ),
+            _ => (22, rest),
+        };
+        Ok(SshTarget {
+            user,
+            host: host.to_string(),
+            port,
+            path: if path.is_empty() { "." } else { path }.to_string(),
+        })
+    }
+}
+
+fn run_remote_listing(target: &str, options: &ListOptions) -> Result<()> {
+    if options.no_network {
+        anyhow::bail!("--remote needs the network but --no-network is set");
+    }
+    reject_remote_options(options, "--remote")?;
+    let target = SshTarget::parse(target)?;
+    let entries = read_sftp_dir(&target, options.remote_accept_new_host)?;
+    let root = format!("{}@{}:{}", target.user, target.host, target.path);
+    output_remote_entries(&root, entries, options)
+}
+
+#[cfg(feature = "remote")]
+fn read_sftp_dir(target: &SshTarget, accept_new_host: bool) -> Result<Vec<RemoteEntry>> {
+    let tcp = std::net::TcpStream::connect((target.host.as_str(), target.port))
+        .with_context(|| format!("Failed to connect to {}:{}", target.host, target.port))?;
+    let mut session = ssh2::Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+    verify_host_key(&session, target, accept_new_host)?;
+    if session.userauth_agent(&target.user).is_err() {
+        let key = env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ssh").join("id_rsa"))
+            .context("No ssh agent identity and $HOME is unset")?;
+        session
+            .userauth_pubkey_file(&target.user, None, &key, None)
+            .with_context(|| format!("Authentication as {} failed", target.user))?;
+    }
+    let sftp = session.sftp()?;
+    let listing = sftp
+        .readdir(Path::new(&target.path))
+        .with_context(|| format!("Failed to read {}", target.path))?;
+    Ok(listing
+        .into_iter()
+        .filter_map(|(path, stat)| {
+            let file_type = stat.file_type();
+            let kind = if file_type.is_dir() {
+                "dir"
+            } else if file_type.is_symlink() {
+                "symlink"
+            } else {
+                "file"
+            };
+            Some(RemoteEntry {
+                name: path.file_name()?.to_string_lossy().to_string(),
+                kind,
+                size: stat.size.unwrap_or(0),
+                modified: stat
+                    .mtime
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+                readonly: stat.perm.is_some_and(|perm| perm & 0o200 == 0),
+            })
+        })
+        .collect())
+}
+
+// Checks the server key against ~/.ssh/known_hosts before any credentials are sent. A changed key
+// is always refused; an unknown host only with --remote-accept-new-host, which also records it.
+#[cfg(feature = "remote")]
+fn verify_host_key(
+    session: &ssh2::Session,
+    target: &SshTarget,
+    accept_new_host: bool,
+) -> Result<()> {
+    use ssh2::{CheckResult, KnownHostFileKind};
+
+    let path = env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".ssh").join("known_hosts"))
+        .context("Cannot locate known_hosts: $HOME is unset")?;
+    let mut known_hosts = session.known_hosts()?;
+    if path.exists() {
+        known_hosts
+            .read_file(&path, KnownHostFileKind::OpenSSH)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+    }
+    let (key, key_type) = session
+        .host_key()
+        .context("Server did not present a host key")?;
+    match known_hosts.check_port(&target.host, target.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound if accept_new_host => {
+            let host = if target.port == 22 {
+                target.host.clone()
+            } else {
+                format!("[{}]:{}", target.host, target.port)
+            };
+            known_hosts.add(&host, key, "added by lz", key_type.into())?;
+            known_hosts
+                .write_file(&path, KnownHostFileKind::OpenSSH)
+                .with_context(|| format!("Failed to update {}", path.display()))
         }
+        CheckResult::NotFound => anyhow::bail!(
+            "Host key for {} is not in {}; connect with ssh first or pass --remote-accept-new-host",
+            target.host,
+            path.display()
+        ),
+        CheckResult::Mismatch => anyhow::bail!(
+            "Host key for {} does not match {}; refusing to connect",
+            target.host,
+            path.display()
+        ),
+        CheckResult::Failure => anyhow::bail!("Failed to check the host key for {}", target.host),
     }
+}
 
-    Ok(())
+#[cfg(not(feature = "remote"))]
+fn read_sftp_dir(target: &SshTarget, _accept_new_host: bool) -> Result<Vec<RemoteEntry>> {
+    anyhow::bail!(
+        "lz was built without the `remote` feature; cannot list {}",
+        target.host
+    )
 }
 
-fn read_entries(dir: &Path, all: bool) -> Result<Vec<EntryInfo>> {
-    let mut out = Vec::new();
-    let read_dir =
-        fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
-    for entry in read_dir {
-        let entry = entry?;
-        let name = entry.file_name();
-        if !all && is_hidden(&name) {
-            continue;
+#[derive(Debug, PartialEq)]
+struct S3Target {
+    bucket: String,
+    prefix: String,
+}
+
+impl S3Target {
+    // `s3://bucket[/prefix]`; a non-empty prefix is listed as a directory, so it gets a trailing `/`.
+    fn parse(uri: &str) -> Result<Self> {
+        let rest = uri
+            .strip_prefix("s3://")
+            .with_context(|| format!("Invalid S3 URI (expected s3://bucket/prefix): {uri}"))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            anyhow::bail!("Invalid S3 URI (missing bucket): {uri}");
         }
-        let path = entry.path();
-        let metadata = fs::symlink_metadata(&path)?;
-        let file_type = metadata.file_type();
-        out.push(EntryInfo {
-            name,
-            path,
-            file_type,
-            modified: metadata.modified().ok(),
-            metadata,
-        });
+        let mut prefix = prefix.to_string();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        Ok(S3Target {
+            bucket: bucket.to_string(),
+            prefix,
+        })
     }
-    Ok(out)
 }
 
-fn is_hidden(name: &OsString) -> bool {
-    let s = name.to_string_lossy();
-    s.starts_with('.')
+fn run_s3_listing(uri: &str, options: &ListOptions) -> Result<()> {
+    if options.no_network {
+        anyhow::bail!("--s3 needs the network but --no-network is set");
+    }
+    reject_remote_options(options, "--s3")?;
+    let target = S3Target::parse(uri)?;
+    let entries = read_s3_prefix(&target, options)?;
+    let root = format!("s3://{}/{}", target.bucket, target.prefix);
+    output_remote_entries(&root, entries, options)
 }
 
-fn sort_entries(entries: &mut [EntryInfo], key: SortKey, reverse: bool) {
-    entries.sort_by(|a, b| {
-        let dir_cmp = b.is_dir().cmp(&a.is_dir());
-        if dir_cmp != Ordering::Equal {
-            return dir_cmp;
+// Lists one level below the prefix: common prefixes (with `/` as delimiter) become directories and
+// objects become files. Credentials and the default region come from the usual AWS sources.
+#[cfg(feature = "s3")]
+fn read_s3_prefix(target: &S3Target, options: &ListOptions) -> Result<Vec<RemoteEntry>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = &options.s3_region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &options.s3_endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let config = loader.load().await;
+        // S3-compatible stores rarely support virtual-hosted bucket names.
+        let s3_config = aws_sdk_s3::config::Builder::from(&config)
+            .force_path_style(options.s3_endpoint.is_some())
+            .build();
+        let client = aws_sdk_s3::Client::from_conf(s3_config);
+
+        let mut entries = Vec::new();
+        let mut token = None;
+        loop {
+            let page = client
+                .list_objects_v2()
+                .bucket(&target.bucket)
+                .prefix(&target.prefix)
+                .delimiter("/")
+                .set_continuation_token(token)
+                .send()
+                .await
+                .with_context(|| format!("Failed to list s3://{}", target.bucket))?;
+            for prefix in page.common_prefixes() {
+                let Some(name) = prefix
+                    .prefix()
+                    .and_then(|p| p.strip_prefix(target.prefix.as_str()))
+                else {
+                    continue;
+                };
+                entries.push(RemoteEntry {
+                    name: name.trim_end_matches('/').to_string(),
+                    kind: "dir",
+                    size: 0,
+                    modified: None,
+                    readonly: false,
+                });
+            }
+            for object in page.contents() {
+                let Some(name) = object
+                    .key()
+                    .and_then(|key| key.strip_prefix(target.prefix.as_str()))
+                    .filter(|name| !name.is_empty())
+                else {
+                    continue;
+                };
+                entries.push(RemoteEntry {
+                    name: name.to_string(),
+                    kind: "file",
+                    size: object
+                        .size()
+                        .and_then(|size| u64::try_from(size).ok())
+                        .unwrap_or(0),
+                    modified: object
+                        .last_modified()
+                        .and_then(|time| SystemTime::try_from(*time).ok()),
+                    readonly: false,
+                });
+            }
+            token = page.next_continuation_token().map(str::to_string);
+            if token.is_none() {
+                break;
+            }
         }
+        Ok(entries)
+    })
+}
 
-        let cmp = match key {
-            SortKey::Name => a
-                .name
-                .to_string_lossy()
-                .to_lowercase()
-                .cmp(&b.name.to_string_lossy().to_lowercase()),
-            SortKey::Size => b.size().cmp(&a.size()),
-            SortKey::Age => b.modified.cmp(&a.modified),
-        };
+#[cfg(not(feature = "s3"))]
+fn read_s3_prefix(target: &S3Target, _options: &ListOptions) -> Result<Vec<RemoteEntry>> {
+    anyhow::bail!(
+        "lz was built without the `s3` feature; cannot list s3://{}",
+        target.bucket
+    )
+}
 
-        if reverse { cmp.reverse() } else { cmp }
-    });
+const RELEASES_URL: &str = "https://api.github.com/repos/milmil7/lz/releases/latest";
+
+fn run_version_check(options: &ListOptions) -> Result<()> {
+    if options.no_network {
+        println!("Version check skipped (--no-network)");
+        return Ok(());
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    let tag = latest_release_tag()?;
+    let latest = tag.trim_start_matches('v');
+    if compare_versions(latest, current) == Ordering::Greater {
+        println!("Update available: lz {current} -> {latest}");
+    } else {
+        println!("lz {current} is up to date (latest release: {latest})");
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-struct DisplayEntry {
-    entry: EntryInfo,
-    prefix: String,
-    rel_path: PathBuf,
+#[cfg(feature = "update-check")]
+fn latest_release_tag() -> Result<String> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+
+    let release: Release = reqwest::blocking::Client::builder()
+        .user_agent(concat!("lz/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(10))
+        .build()?
+        .get(RELEASES_URL)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .context("Failed to fetch the latest release")?;
+    Ok(release.tag_name)
 }
 
-fn compile_filter(options: &ListOptions) -> Result<Option<GlobMatcher>> {
-    let Some(pattern) = options.filter.as_deref() else {
-        return Ok(None);
-    };
-    let glob = Glob::new(pattern).with_context(|| format!("Invalid glob: {pattern}"))?;
-    Ok(Some(glob.compile_matcher()))
+#[cfg(not(feature = "update-check"))]
+fn latest_release_tag() -> Result<String> {
+    anyhow::bail!("lz was built without the `update-check` feature; cannot query {RELEASES_URL}")
 }
 
-fn build_display_entries_for_dir(
-    dir: &Path,
-    root: &Path,
-    options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
-) -> Result<Vec<DisplayEntry>> {
-    if options.tree {
-        let md = fs::symlink_metadata(dir)?;
-        let root_entry = EntryInfo {
-            name: dir
-                .file_name()
-                .map(OsString::from)
-                .unwrap_or_else(|| OsString::from(dir.as_os_str())),
-            path: dir.to_path_buf(),
-            file_type: md.file_type(),
-            modified: md.modified().ok(),
-            metadata: md,
-        };
+// Numeric dotted comparison; pre-release and build suffixes are ignored.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
 
-        let mut out = Vec::new();
-        let root_rel = PathBuf::from(".");
-        if !options.only_files {
-            out.push(DisplayEntry {
-                entry: root_entry,
-                prefix: String::new(),
-                rel_path: root_rel,
-            });
-        }
-        let mut ancestor_more = Vec::new();
-        collect_tree_children(dir, root, options, matcher, &mut ancestor_more, &mut out)?;
-        Ok(out)
-    } else {
-        let mut entries = read_entries(dir, options.all)?;
-        sort_entries(&mut entries, options.sort, options.reverse);
-        let mut out = Vec::new();
-        for entry in entries {
-            let rel_path = entry
-                .path
-                .strip_prefix(root)
-                .unwrap_or(&entry.path)
-                .to_path_buf();
+#[derive(Debug, Default)]
+struct TreeStats {
+    files: u64,
+    dirs: u64,
+    bytes: u64,
+}
 
-            if !should_print_entry(&entry, &rel_path, options, matcher) {
-                continue;
-            }
+fn run_cp(src: &Path, dst: &Path, options: &ListOptions) -> Result<()> {
+    let start = Instant::now();
+    let dest = resolve_destination(src, dst)?;
+    let progress = io::stdout().is_tty() && !options.no_progress;
+    let mut stats = TreeStats::default();
+    let result = copy_path(src, &dest, &mut stats, progress);
+    if progress {
+        clear_progress_line();
+    }
+    result?;
+    outln!(
+        "Copied {} files, {} dirs, total {} in {:.2} seconds",
+        stats.files,
+        stats.dirs,
+        format_size(stats.bytes, true),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
 
-            out.push(DisplayEntry {
-                entry,
-                prefix: String::new(),
-                rel_path,
-            });
+fn run_mv(src: &Path, dst: &Path, options: &ListOptions) -> Result<()> {
+    let dest = resolve_destination(src, dst)?;
+    match fs::rename(src, &dest) {
+        Ok(()) => {
+            outln!("Moved {} to {}", src.display(), dest.display());
+            return Ok(());
+        }
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to move {}", src.display()));
         }
-        Ok(out)
     }
-}
 
-fn collect_tree_children(
-    dir: &Path,
+    // Different filesystem: copy, then remove the source only once the copy has fully succeeded.
+    let start = Instant::now();
+    let progress = io::stdout().is_tty() && !options.no_progress;
+    let mut stats = TreeStats::default();
+    let result = copy_path(src, &dest, &mut stats, progress);
+    if progress {
+        clear_progress_line();
+    }
+    result?;
+    if fs::symlink_metadata(src)?.is_dir() {
+        fs::remove_dir_all(src)
+    } else {
+        fs::remove_file(src)
+    }
+    .with_context(|| format!("Copied but failed to remove {}", src.display()))?;
+    outln!(
+        "Moved {} files, {} dirs, total {} in {:.2} seconds",
+        stats.files,
+        stats.dirs,
+        format_size(stats.bytes, true),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn run_mkdir(args: &MkdirArgs, options: &ListOptions) -> Result<()> {
+    let created = if args.parents {
+        fs::create_dir_all(&args.path)
+    } else {
+        fs::create_dir(&args.path)
+    };
+    created.with_context(|| format!("✗ Failed to create {}", args.path.display()))?;
+
+    let full = fs::canonicalize(&args.path).unwrap_or_else(|_| args.path.clone());
+    outln!(
+        "{}",
+        paint(format!("✓ {}", full.display()), Style::new().bright_green())
+    );
+    if args.list {
+        list_path(&args.path, options)?;
+    }
+    Ok(())
+}
+
+fn run_rm(args: &RmArgs) -> Result<()> {
+    let path = &args.path;
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("Cannot access {}", path.display()))?;
+    let is_dir = metadata.is_dir();
+    if is_dir && !args.recursive {
+        anyhow::bail!("{} is a directory (use -R to remove it)", path.display());
+    }
+
+    let mut stats = TreeStats::default();
+    tally_tree(path, &mut stats)?;
+    let what = format!(
+        "{} files, {} dirs, {}",
+        stats.files,
+        stats.dirs,
+        format_size(stats.bytes, true)
+    );
+
+    if !args.force {
+        let action = if args.trash {
+            "Move to trash"
+        } else {
+            "Permanently remove"
+        };
+        eprint!("{action} {} ({what})? [y/N] ", path.display());
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            outln!("Aborted");
+            return Ok(());
+        }
+    }
+
+    if args.trash {
+        trash::delete(path).with_context(|| format!("Failed to trash {}", path.display()))?;
+        outln!("Moved {} to trash ({what})", path.display());
+    } else {
+        if is_dir {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .with_context(|| format!("Failed to remove {}", path.display()))?;
+        outln!("Removed {} ({what})", path.display());
+    }
+    Ok(())
+}
+
+fn run_touch(args: &TouchArgs) -> Result<()> {
+    let path = &args.path;
+    let time = match &args.timestamp {
+        Some(text) => humantime::parse_rfc3339_weak(text).with_context(|| {
+            format!("Invalid timestamp: {text} (expected e.g. 2024-03-15T12:00:00Z)")
+        })?,
+        None => SystemTime::now(),
+    };
+
+    let existed = fs::symlink_metadata(path).is_ok();
+    let file = if existed {
+        fs::File::open(path)
+    } else if args.no_create {
+        outln!("{} does not exist; not created", path.display());
+        return Ok(());
+    } else {
+        fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+    }
+    .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    file.set_times(fs::FileTimes::new().set_accessed(time).set_modified(time))
+        .with_context(|| format!("Failed to update timestamps of {}", path.display()))?;
+
+    let verb = if existed { "Updated" } else { "Created" };
+    outln!(
+        "{verb} {} ({})",
+        path.display(),
+        humantime::format_rfc3339_seconds(time)
+    );
+    Ok(())
+}
+
+#[derive(Debug)]
+struct SizeNode {
+    name: String,
+    is_dir: bool,
+    bytes: u64,
+    children: Vec<SizeNode>,
+}
+
+fn run_size(path: &Path, recursive: bool, options: &ListOptions) -> Result<()> {
+    let root = size_node(path)?;
+    if !root.is_dir {
+        outln!("{}  {}", root.name, format_size(root.bytes, true));
+        return Ok(());
+    }
+    print_size_children(&root, 0, recursive, options);
+    outln!(
+        "{} {}",
+        paint("Total:", Style::new().bright_yellow()),
+        paint(format_size(root.bytes, true), Style::new().bright_yellow())
+    );
+    Ok(())
+}
+
+#[derive(Debug)]
+struct MountEntry {
+    mount_point: PathBuf,
+    fs_type: String,
+}
+
+// Pseudo filesystems (proc, sysfs, cgroup, ...) report no blocks and are only shown with --all.
+fn run_list_roots(options: &ListOptions) {
+    let rows: Vec<_> = list_roots()
+        .into_iter()
+        .filter_map(|root| {
+            let space = disk_space(&root.mount_point);
+            if !options.all && space.is_some_and(|(total, _)| total == 0) {
+                return None;
+            }
+            let free = space.map_or_else(
+                || "-".to_string(),
+                |(_, available)| format!("{} free", format_size(available, true)),
+            );
+            Some((root.mount_point.display().to_string(), root.fs_type, free))
+        })
+        .collect();
+    let path_w = rows
+        .iter()
+        .map(|(path, ..)| path.chars().count())
+        .max()
+        .unwrap_or(0);
+    let type_w = rows
+        .iter()
+        .map(|(_, t, _)| t.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (path, fs_type, free) in rows {
+        outln!(
+            "{}  {}  {}",
+            paint(format!("{path:<path_w$}"), Style::new().bright_blue()),
+            paint(format!("{fs_type:<type_w$}"), Style::new().bright_black()),
+            paint(free, Style::new().bright_magenta())
+        );
+    }
+}
+
+#[cfg(unix)]
+fn list_roots() -> Vec<MountEntry> {
+    let mounts = fs::read_to_string("/proc/mounts")
+        .map(|text| parse_mounts(&text))
+        .unwrap_or_default();
+    if mounts.is_empty() {
+        return vec![MountEntry {
+            mount_point: PathBuf::from("/"),
+            fs_type: "-".to_string(),
+        }];
+    }
+    mounts
+}
+
+#[cfg(not(unix))]
+fn list_roots() -> Vec<MountEntry> {
+    ('A'..='Z')
+        .map(|letter| PathBuf::from(format!("{letter}:\\")))
+        .filter(|root| root.exists())
+        .map(|mount_point| MountEntry {
+            mount_point,
+            fs_type: "-".to_string(),
+        })
+        .collect()
+}
+
+fn parse_mounts(text: &str) -> Vec<MountEntry> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = unescape_mount_field(fields.next()?);
+            let fs_type = fields.next()?.to_string();
+            Some(MountEntry {
+                mount_point: PathBuf::from(mount_point),
+                fs_type,
+            })
+        })
+        .collect()
+}
+
+// The kernel writes space, tab, newline and backslash in mount paths as three-digit octal escapes.
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let escape = rest.get(pos + 1..pos + 4);
+        match escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Returns (total, available) bytes of the filesystem holding `path`.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    let fragment = stat.fragment_size() as u64;
+    Some((
+        stat.blocks() as u64 * fragment,
+        stat.blocks_available() as u64 * fragment,
+    ))
+}
+
+#[cfg(not(unix))]
+fn disk_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+// Returns the filesystem type and free space column text for `--mount-info`. Results are cached
+// per device, so statvfs and the mount table lookup run once per filesystem.
+#[cfg(unix)]
+fn mount_info(entry: &EntryInfo) -> (String, String) {
+    use std::os::unix::fs::MetadataExt;
+
+    static CACHE: OnceLock<Mutex<HashMap<u64, (String, String)>>> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    cache
+        .entry(entry.metadata.dev())
+        .or_insert_with(|| {
+            // A symlink lives on its parent's filesystem, not its target's.
+            let path = match entry.path.parent() {
+                Some(parent) if entry.is_symlink() => parent,
+                _ => &entry.path,
+            };
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            let fs_type = list_roots()
+                .into_iter()
+                .filter(|root| path.starts_with(&root.mount_point))
+                .max_by_key(|root| root.mount_point.components().count())
+                .map_or_else(|| "-".to_string(), |root| root.fs_type);
+            let free = disk_space(&path).map_or_else(
+                || "-".to_string(),
+                |(_, available)| format!("{} free", format_size(available, true)),
+            );
+            (fs_type, free)
+        })
+        .clone()
+}
+
+#[cfg(not(unix))]
+fn mount_info(_entry: &EntryInfo) -> (String, String) {
+    ("-".to_string(), "-".to_string())
+}
+
+fn size_node(path: &Path) -> Result<SizeNode> {
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("Cannot access {}", path.display()))?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    if !metadata.is_dir() {
+        return Ok(SizeNode {
+            name,
+            is_dir: false,
+            bytes: metadata.len(),
+            children: Vec::new(),
+        });
+    }
+
+    let mut children = Vec::new();
+    for entry in fs::read_dir(path)? {
+        children.push(size_node(&entry?.path())?);
+    }
+    children.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    Ok(SizeNode {
+        name,
+        is_dir: true,
+        bytes: children.iter().map(|c| c.bytes).sum(),
+        children,
+    })
+}
+
+fn print_size_children(node: &SizeNode, depth: usize, recursive: bool, options: &ListOptions) {
+    const BAR_W: usize = 20;
+
+    let indent = "  ".repeat(depth);
+    let labels: Vec<String> = node
+        .children
+        .iter()
+        .map(|c| {
+            if c.is_dir {
+                format!("{}{}", c.name, std::path::MAIN_SEPARATOR)
+            } else {
+                c.name.clone()
+            }
+        })
+        .collect();
+    let sizes: Vec<String> = node
+        .children
+        .iter()
+        .map(|c| format_size(c.bytes, true))
+        .collect();
+    let name_w = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let size_w = sizes.iter().map(|s| s.len()).max().unwrap_or(0);
+
+    for ((child, label), size) in node.children.iter().zip(&labels).zip(&sizes) {
+        let ratio = if node.bytes == 0 {
+            0.0
+        } else {
+            child.bytes as f64 / node.bytes as f64
+        };
+        let filled = ((ratio * BAR_W as f64).round() as usize).min(BAR_W);
+        let bar = format!("{}{}", "█".repeat(filled), "░".repeat(BAR_W - filled));
+        let pad = name_w - label.chars().count();
+        let label = if child.is_dir {
+            paint(label, options.colors.dir.style())
+        } else {
+            label.clone()
+        };
+        outln!(
+            "{indent}{label}{:pad$}  {}  [{}] {:>3.0}%",
+            "",
+            paint(format!("{size:>size_w$}"), Style::new().bright_magenta()),
+            paint(bar, Style::new().bright_cyan()),
+            ratio * 100.0
+        );
+        if recursive && child.is_dir {
+            print_size_children(child, depth + 1, recursive, options);
+        }
+    }
+}
+
+fn tally_tree(path: &Path, stats: &mut TreeStats) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        stats.dirs += 1;
+        for entry in fs::read_dir(path)? {
+            tally_tree(&entry?.path(), stats)?;
+        }
+    } else {
+        stats.files += 1;
+        stats.bytes += metadata.len();
+    }
+    Ok(())
+}
+
+// Like cp/mv: an existing directory as destination means "into it". Nothing is ever overwritten.
+fn resolve_destination(src: &Path, dst: &Path) -> Result<PathBuf> {
+    fs::symlink_metadata(src).with_context(|| format!("Cannot access {}", src.display()))?;
+    let dest = if dst.is_dir() {
+        let name = src
+            .file_name()
+            .with_context(|| format!("{} has no file name", src.display()))?;
+        dst.join(name)
+    } else {
+        dst.to_path_buf()
+    };
+    if fs::symlink_metadata(&dest).is_ok() {
+        anyhow::bail!("{} already exists", dest.display());
+    }
+    if let (Ok(src), Some(parent)) = (fs::canonicalize(src), dest.parent()) {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if fs::canonicalize(parent).is_ok_and(|p| p.starts_with(&src)) {
+            anyhow::bail!("Cannot place {} inside itself", src.display());
+        }
+    }
+    Ok(dest)
+}
+
+fn copy_path(src: &Path, dst: &Path, stats: &mut TreeStats, progress: bool) -> Result<()> {
+    let metadata = fs::symlink_metadata(src)?;
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(dst).with_context(|| format!("Failed to create {}", dst.display()))?;
+        stats.dirs += 1;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &dst.join(entry.file_name()), stats, progress)?;
+        }
+        // Applied last so read-only directories can still be filled.
+        fs::set_permissions(dst, metadata.permissions())?;
+    } else if file_type.is_symlink() {
+        copy_symlink(src, dst)?;
+        stats.files += 1;
+    } else {
+        copy_file_with_progress(src, dst, metadata.len(), progress)
+            .with_context(|| format!("Failed to copy {}", src.display()))?;
+        fs::set_permissions(dst, metadata.permissions())?;
+        stats.files += 1;
+        stats.bytes += metadata.len();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dst)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    fs::copy(src, dst)?;
+    Ok(())
+}
+
+fn copy_file_with_progress(src: &Path, dst: &Path, total: u64, progress: bool) -> Result<()> {
+    let name = src
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| src.display().to_string());
+    let mut reader = io::BufReader::new(fs::File::open(src)?);
+    let mut writer = io::BufWriter::new(fs::File::create(dst)?);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut copied = 0u64;
+    let mut last_report: Option<Instant> = None;
+
+    loop {
+        if progress && last_report.is_none_or(|t| t.elapsed() >= Duration::from_millis(100)) {
+            print_copy_progress(&name, copied, total);
+            last_report = Some(Instant::now());
+        }
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_copy_progress(name: &str, copied: u64, total: u64) {
+    clear_progress_line();
+    print!(
+        "Copying {name} ({} / {})",
+        format_size(copied, true),
+        format_size(total, true)
+    );
+    let _ = io::stdout().flush();
+}
+
+fn clear_progress_line() {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(MoveToColumn(0));
+    let _ = stdout.execute(Clear(ClearType::CurrentLine));
+}
+
+fn run_fastls(mode: FastlsMode, options: ListOptions) -> Result<()> {
+    let picked = match mode {
+        FastlsMode::Folder => rfd::FileDialog::new()
+            .set_title("Choose folders to list")
+            .pick_folders(),
+        FastlsMode::File => rfd::FileDialog::new()
+            .set_title("Choose files to inspect")
+            .pick_files(),
+    };
+    list_paths(&picked.unwrap_or_default(), &options)
+}
+
+fn run_benchmark(path: &Path, runs: u64, options: &ListOptions) -> Result<()> {
+    let mut samples = Vec::with_capacity(runs as usize);
+    for _ in 0..runs {
+        let start = Instant::now();
+        collect_listing(path, options)?;
+        samples.push(start.elapsed());
+    }
+    samples.sort();
+
+    let total: Duration = samples.iter().sum();
+    let mean = total / samples.len() as u32;
+    let mid = samples.len() / 2;
+    let median = if samples.len() % 2 == 0 {
+        (samples[mid - 1] + samples[mid]) / 2
+    } else {
+        samples[mid]
+    };
+    let p95 = samples[(samples.len() * 95).div_ceil(100) - 1];
+
+    let rows = [
+        ("runs", runs.to_string()),
+        ("mean", format_duration_ms(mean)),
+        ("median", format_duration_ms(median)),
+        ("p95", format_duration_ms(p95)),
+        ("min", format_duration_ms(samples[0])),
+        ("max", format_duration_ms(samples[samples.len() - 1])),
+    ];
+    for (label, value) in rows {
+        outln!(
+            "{:<8}{:>12}",
+            paint(label, Style::new().bright_blue()),
+            paint(value, Style::new().bright_white())
+        );
+    }
+    Ok(())
+}
+
+fn format_duration_ms(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+fn list_paths(paths: &[PathBuf], options: &ListOptions) -> Result<()> {
+    let multiple = paths.len() > 1;
+    let mut summaries = if options.concurrent_summary
+        && multiple
+        && !options.watch
+        && (options.du || options.extensions)
+    {
+        compute_summaries_concurrently(paths, options)?
+    } else {
+        Vec::new()
+    }
+    .into_iter();
+
+    for path in paths {
+        if multiple && !options.json {
+            outln!(
+                "{}",
+                paint(
+                    format!("=== {} ===", path.display()),
+                    Style::new().bright_yellow()
+                )
+            );
+            if !options.print_dir {
+                print_header_gap(options);
+            }
+        }
+        match summaries.next().flatten() {
+            Some(summary) => list_path_once(path, options, None, Some(summary))?,
+            None => list_path(path, options)?,
+        }
+    }
+    Ok(())
+}
+
+// Paths whose summary fails come back as `None` and are listed normally, which reports the error.
+fn compute_summaries_concurrently(
+    paths: &[PathBuf],
+    options: &ListOptions,
+) -> Result<Vec<Option<ListingSummary>>> {
+    let matcher = compile_filter(options)?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.concurrent_limit.unwrap_or(0))
+        .build()
+        .context("Failed to start summary thread pool")?;
+    Ok(pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| compute_summary(path, options, matcher.as_ref()).ok())
+            .collect()
+    }))
+}
+
+fn print_header_gap(options: &ListOptions) {
+    for _ in 0..options.header_gap {
+        outln!();
+    }
+}
+
+fn list_path(path: &Path, options: &ListOptions) -> Result<()> {
+    if options.watch && (options.watch_diff || options.watch_events) && !options.json {
+        watch_changes(path, options)
+    } else if options.watch {
+        for refresh_id in 0u64.. {
+            if !options.json {
+                // The previous refresh's held-back newline would otherwise open the new screen.
+                output().pending_newline = false;
+                let mut stdout = io::stdout();
+                stdout.execute(Clear(ClearType::All))?;
+                stdout.execute(MoveTo(0, 0))?;
+            }
+
+            if let Err(err) = list_path_once(path, options, Some(refresh_id), None) {
+                if options.json {
+                    let out = JsonOutput {
+                        version: JSON_FORMAT_VERSION,
+                        refresh_id: Some(refresh_id),
+                        root: path.display().to_string(),
+                        entries: Vec::new(),
+                        summary: None,
+                        error: Some(format!("{err:#}")),
+                    };
+                    outln!("{}", serde_json::to_string(&out)?);
+                } else {
+                    report_error(&err);
+                }
+            }
+
+            if options.json {
+                io::stdout().flush()?;
+            }
+
+            thread::sleep(Duration::from_secs(2));
+        }
+        Ok(())
+    } else {
+        list_path_once(path, options, None, None)
+    }
+}
+
+fn watch_changes(path: &Path, options: &ListOptions) -> Result<()> {
+    let mut previous: Option<Vec<DisplayEntry>> = None;
+    loop {
+        match collect_listing(path, options) {
+            Ok((entries, summary)) => {
+                match &previous {
+                    None if options.watch_events => {}
+                    None => output_entries(path, &entries, summary.as_ref(), options, None)?,
+                    Some(prev) => {
+                        for (change, entry) in diff_listings(prev, &entries) {
+                            print_listing_change(change, entry, options);
+                        }
+                    }
+                }
+                previous = Some(entries);
+            }
+            Err(err) => report_error(&err),
+        }
+
+        io::stdout().flush()?;
+        thread::sleep(Duration::from_secs(2));
+    }
+}
+
+fn print_listing_change(change: ListingChange, entry: &DisplayEntry, options: &ListOptions) {
+    let rel = normalize_match_path(&entry.rel_path);
+    let line = if options.watch_events {
+        let label = match change {
+            ListingChange::Added => "[ADDED]",
+            ListingChange::Removed => "[REMOVED]",
+            ListingChange::Modified => "[MODIFIED]",
+        };
+        let now = format_timestamp(SystemTime::now(), options, false);
+        format!("{now} {label} {rel}")
+    } else {
+        let marker = match change {
+            ListingChange::Added => '+',
+            ListingChange::Removed => '-',
+            ListingChange::Modified => '~',
+        };
+        format!("{marker}{rel}")
+    };
+
+    match change {
+        ListingChange::Added => outln!("{}", paint(line, Style::new().bright_green())),
+        ListingChange::Removed => outln!("{}", paint(line, Style::new().bright_red())),
+        ListingChange::Modified => outln!("{}", paint(line, Style::new().bright_yellow())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListingChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+fn diff_listings<'a>(
+    previous: &'a [DisplayEntry],
+    current: &'a [DisplayEntry],
+) -> Vec<(ListingChange, &'a DisplayEntry)> {
+    let before: BTreeMap<&Path, &DisplayEntry> =
+        previous.iter().map(|e| (e.rel_path.as_path(), e)).collect();
+    let after: BTreeMap<&Path, &DisplayEntry> =
+        current.iter().map(|e| (e.rel_path.as_path(), e)).collect();
+
+    let mut changes = Vec::new();
+    for (rel, old) in &before {
+        match after.get(rel) {
+            None => changes.push((ListingChange::Removed, *old)),
+            Some(new) => {
+                if old.entry.is_dir() != new.entry.is_dir()
+                    || old.entry.size() != new.entry.size()
+                    || old.entry.modified != new.entry.modified
+                {
+                    changes.push((ListingChange::Modified, *new));
+                }
+            }
+        }
+    }
+    for (rel, new) in &after {
+        if !before.contains_key(rel) {
+            changes.push((ListingChange::Added, *new));
+        }
+    }
+    changes.sort_by(|a, b| a.1.rel_path.cmp(&b.1.rel_path));
+    changes
+}
+
+fn list_path_once(
+    path: &Path,
+    options: &ListOptions,
+    refresh_id: Option<u64>,
+    precomputed: Option<ListingSummary>,
+) -> Result<()> {
+    PHASE_TIMINGS.with(|t| t.set([Duration::ZERO; 4]));
+    clear_author_cache();
+    let (entries, summary) = collect_listing_with(path, options, precomputed)?;
+    timed(Phase::Output, || {
+        output_entries(path, &entries, summary.as_ref(), options, refresh_id)
+    })?;
+    log_operation("list", path, Some(entries.len()));
+    if options.profile {
+        print_phase_timings();
+    }
+    if options.profile_memory {
+        print_memory_stats();
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Read,
+    Sort,
+    Summary,
+    Output,
+}
+
+thread_local! {
+    static PHASE_TIMINGS: Cell<[Duration; 4]> = const { Cell::new([Duration::ZERO; 4]) };
+}
+
+fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    let elapsed = start.elapsed();
+    PHASE_TIMINGS.with(|t| {
+        let mut timings = t.get();
+        timings[phase as usize] += elapsed;
+        t.set(timings);
+    });
+    out
+}
+
+fn print_phase_timings() {
+    let timings = PHASE_TIMINGS.with(Cell::get);
+    let ms = |phase: Phase| timings[phase as usize].as_secs_f64() * 1000.0;
+    let line = format!(
+        "read: {:.1}ms  sort: {:.1}ms  summary: {:.1}ms  output: {:.1}ms",
+        ms(Phase::Read),
+        ms(Phase::Sort),
+        ms(Phase::Summary),
+        ms(Phase::Output)
+    );
+    eprintln!("{}", paint(line, Style::new().bright_black()));
+}
+
+fn collect_listing(
+    path: &Path,
+    options: &ListOptions,
+) -> Result<(Vec<DisplayEntry>, Option<ListingSummary>)> {
+    collect_listing_with(path, options, None)
+}
+
+fn collect_listing_with(
+    path: &Path,
+    options: &ListOptions,
+    precomputed: Option<ListingSummary>,
+) -> Result<(Vec<DisplayEntry>, Option<ListingSummary>)> {
+    let matcher = compile_filter(options)?;
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata for {}", path.display()))?;
+
+    let summary = match precomputed {
+        Some(summary) => Some(summary),
+        None if options.du || options.extensions => Some(timed(Phase::Summary, || {
+            compute_summary(path, options, matcher.as_ref())
+        })?),
+        None => None,
+    };
+
+    if metadata.is_dir() {
+        let mut entries = build_display_entries_for_dir(path, path, options, matcher.as_ref())?;
+        if options.hardlink_info {
+            assign_hardlink_ids(&mut entries);
+        }
+        Ok((entries, summary))
+    } else {
+        let file_type = metadata.file_type();
+        let name = path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_else(|| OsString::from(path.as_os_str()));
+        let entry = EntryInfo {
+            name,
+            path: path.to_path_buf(),
+            file_type,
+            modified: metadata.modified().ok(),
+            metadata,
+        };
+        let rel_path = path
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| path.to_path_buf());
+        let display = DisplayEntry {
+            entry,
+            prefix: String::new(),
+            rel_path,
+            entry_count: None,
+            subtree_bytes: None,
+            hardlink_id: None,
+        };
+        Ok((vec![display], summary))
+    }
+}
+
+fn output_entries(
+    root: &Path,
+    entries: &[DisplayEntry],
+    summary: Option<&ListingSummary>,
+    options: &ListOptions,
+    refresh_id: Option<u64>,
+) -> Result<()> {
+    if options.json || options.xml || options.toml_output || options.yaml_output {
+        let out = JsonOutput {
+            version: JSON_FORMAT_VERSION,
+            refresh_id,
+            root: root.display().to_string(),
+            entries: if options.tree_json {
+                build_nested_json(entries)
+            } else {
+                entries.iter().map(|e| e.to_json()).collect()
+            },
+            summary: summary.map(|s| s.to_json(options.extensions)),
+            error: None,
+        };
+        return output_structured(&out, options);
+    }
+
+    if options.export_env
+        && let Some(summary) = summary
+    {
+        outln!("LZ_TOTAL_BYTES={}", summary.total_bytes);
+        outln!("LZ_TOTAL_FILES={}", summary.total_files);
+        outln!("LZ_TOTAL_DIRS={}", summary.total_dirs);
+        return Ok(());
+    }
+
+    if options.print_dir {
+        outln!(
+            "{}",
+            paint(format!("{}:", root.display()), Style::new().bright_blue())
+        );
+        print_header_gap(options);
+    }
+
+    let groups = if options.group_by_ext {
+        Some(group_by_extension(entries))
+    } else if options.group_by_date {
+        Some(group_by_date(entries))
+    } else {
+        None
+    };
+
+    if let Some(groups) = groups {
+        for (header, group) in groups {
+            outln!(
+                "{}",
+                paint(format!("--- {header} ---"), Style::new().bright_yellow())
+            );
+            print_entries(&group, options)?;
+        }
+    } else {
+        print_entries(entries, options)?;
+    }
+
+    if options.hidden_count && !options.all && !options.hidden_only && root.is_dir() {
+        let hidden = count_hidden(root, options.tree)?;
+        if hidden > 0 {
+            let noun = if hidden == 1 { "entry" } else { "entries" };
+            outln!(
+                "{}",
+                paint(
+                    format!("({hidden} hidden {noun}; use --all to show)"),
+                    Style::new().bright_black()
+                )
+            );
+        }
+    }
+
+    if options.dry_run {
+        outln!(
+            "{}",
+            paint(
+                "(dry run - metadata from the directory listing, not re-read)",
+                Style::new().bright_black()
+            )
+        );
+    }
+
+    if let Some(summary) = summary {
+        print_summary(summary, options);
+    }
+
+    Ok(())
+}
+
+fn output_structured(out: &JsonOutput, options: &ListOptions) -> Result<()> {
+    if options.xml {
+        outln!("{}", output_entries_xml(out)?);
+    } else if options.toml_output {
+        outln!("{}", output_entries_toml(out)?);
+    } else if options.yaml_output {
+        outln!("{}", output_entries_yaml(out, options.yaml_flow)?);
+    } else if options.json_stream {
+        outln!("{}", serde_json::to_string(out)?);
+    } else {
+        outln!("{}", serde_json::to_string_pretty(out)?);
+    }
+    Ok(())
+}
+
+fn print_entries(entries: &[DisplayEntry], options: &ListOptions) -> Result<()> {
+    if options.long {
+        print_long(entries, options)?;
+    } else if options.wide {
+        print_wide(entries, options);
+    } else {
+        for entry in entries {
+            outln!("{}", display_name(entry, options));
+        }
+    }
+    Ok(())
+}
+
+fn group_by_extension(entries: &[DisplayEntry]) -> Vec<(String, Vec<DisplayEntry>)> {
+    let mut dirs = Vec::new();
+    let mut by_ext: BTreeMap<String, Vec<DisplayEntry>> = BTreeMap::new();
+    for entry in entries {
+        if entry.entry.is_dir() {
+            dirs.push(entry.clone());
+            continue;
+        }
+        let ext = entry
+            .entry
+            .path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        by_ext.entry(ext).or_default().push(entry.clone());
+    }
+
+    let mut groups = Vec::new();
+    if !dirs.is_empty() {
+        groups.push(("directories".to_string(), dirs));
+    }
+    groups.extend(
+        by_ext
+            .into_iter()
+            .map(|(ext, group)| (ext_label(&ext), group)),
+    );
+    groups
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DateGroup {
+    Today,
+    ThisWeek,
+    LastMonth,
+    Older,
+}
+
+impl DateGroup {
+    fn label(self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::ThisWeek => "This week",
+            DateGroup::LastMonth => "Last month",
+            DateGroup::Older => "Older",
+        }
+    }
+}
+
+fn date_group(t: SystemTime) -> DateGroup {
+    let today = Local::now().date_naive();
+    let day = DateTime::<Local>::from(t).date_naive();
+    if day >= today {
+        DateGroup::Today
+    } else if day > today - Days::new(7) {
+        DateGroup::ThisWeek
+    } else if day > today - Days::new(30) {
+        DateGroup::LastMonth
+    } else {
+        DateGroup::Older
+    }
+}
+
+fn group_by_date(entries: &[DisplayEntry]) -> Vec<(String, Vec<DisplayEntry>)> {
+    let mut groups: BTreeMap<DateGroup, Vec<DisplayEntry>> = BTreeMap::new();
+    for entry in entries {
+        let group = entry.entry.modified.map_or(DateGroup::Older, date_group);
+        groups.entry(group).or_default().push(entry.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(group, entries)| (group.label().to_string(), entries))
+        .collect()
+}
+
+fn print_summary(summary: &ListingSummary, options: &ListOptions) {
+    match options.summary_format {
+        SummaryFormat::None => {}
+        SummaryFormat::Inline => {
+            let sep = thousands_separator();
+            let mut line = format!(
+                "{} files, {} dirs, {}",
+                format_integer_with_separators(summary.total_files, sep),
+                format_integer_with_separators(summary.total_dirs, sep),
+                format_size(summary.total_bytes, true)
+            );
+            if options.extensions && !summary.ext.is_empty() {
+                let exts: Vec<String> = summary
+                    .ext
+                    .iter()
+                    .map(|(ext, s)| format!("{}: {}", ext_label(ext), s.files))
+                    .collect();
+                line.push_str(&format!(" [{}]", exts.join(", ")));
+            }
+            outln!("{}", paint(line, Style::new().bright_yellow()));
+        }
+        SummaryFormat::Table => {
+            if options.du {
+                outln!(
+                    "{} {}",
+                    paint("Total:", Style::new().bright_yellow()),
+                    paint(
+                        format_size(summary.total_bytes, true),
+                        Style::new().bright_yellow()
+                    )
+                );
+            }
+            if options.extensions && options.ext_chart {
+                print_ext_chart(summary, options.chart_width);
+            } else if options.extensions {
+                for (ext, s) in &summary.ext {
+                    let files = format!(
+                        "{} files",
+                        format_integer_with_separators(s.files, thousands_separator())
+                    );
+                    let bytes = format_size(s.bytes, true);
+                    outln!(
+                        "{}  {}  {}",
+                        paint(ext_label(ext), Style::new().bright_blue()),
+                        paint(files, Style::new().bright_white()),
+                        paint(bytes, Style::new().bright_magenta())
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn print_ext_chart(summary: &ListingSummary, width: usize) {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+    let mut exts: Vec<(&String, &ExtSummary)> = summary.ext.iter().collect();
+    exts.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+    exts.truncate(10);
+    let max = exts.first().map(|(_, s)| s.bytes).unwrap_or(0).max(1);
+
+    for (ext, s) in exts {
+        let label: String = ext_label(ext).chars().take(8).collect();
+        let eighths = (s.bytes as u128 * width as u128 * 8 / max as u128) as usize;
+        let mut bar = "█".repeat(eighths / 8);
+        let rem = eighths % 8;
+        if rem > 0 {
+            bar.push(PARTIAL[rem]);
+        }
+        outln!(
+            "{}  {}  {}",
+            paint(format!("{label:<8}"), Style::new().bright_blue()),
+            paint(format!("{bar:<width$}"), Style::new().bright_magenta()),
+            paint(format_size(s.bytes, true), Style::new().bright_white())
+        );
+    }
+}
+
+fn ext_label(ext: &str) -> String {
+    if ext.is_empty() {
+        "(none)".to_string()
+    } else {
+        format!(".{ext}")
+    }
+}
+
+fn read_entries(dir: &Path, options: &ListOptions) -> Result<Vec<EntryInfo>> {
+    let mut out = Vec::new();
+    let read_dir =
+        fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in read_dir {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !options.all && !options.hidden_only && is_hidden(&name) {
+            continue;
+        }
+        let path = entry.path();
+        let metadata = if options.dry_run {
+            entry.metadata()?
+        } else {
+            fs::symlink_metadata(&path)?
+        };
+        let file_type = metadata.file_type();
+        out.push(EntryInfo {
+            name,
+            path,
+            file_type,
+            modified: metadata.modified().ok(),
+            metadata,
+        });
+    }
+    Ok(out)
+}
+
+fn count_hidden(dir: &Path, recursive: bool) -> Result<u64> {
+    let mut hidden = 0u64;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if is_hidden(&entry.file_name()) {
+            hidden += 1;
+        } else if recursive && entry.file_type()?.is_dir() {
+            hidden += count_hidden(&entry.path(), recursive)?;
+        }
+    }
+    Ok(hidden)
+}
+
+fn is_hidden(name: &OsStr) -> bool {
+    let s = name.to_string_lossy();
+    s.starts_with('.')
+}
+
+fn sort_listing<E: ListingEntry>(
+    entries: &mut [E],
+    options: &ListOptions,
+    dir_sizes: Option<&HashMap<PathBuf, u64>>,
+) {
+    if !options.no_sort {
+        sort_entries(
+            entries,
+            options.sort,
+            options.reverse,
+            options.entries_first,
+            dir_sizes,
+        );
+    }
+}
+
+// `dir_sizes` holds subtree totals for directories; without it they sort by size as 0.
+fn sort_entries<E: ListingEntry>(
+    entries: &mut [E],
+    key: SortKey,
+    reverse: bool,
+    entries_first: bool,
+    dir_sizes: Option<&HashMap<PathBuf, u64>>,
+) {
+    let size = |entry: &E| match dir_sizes.filter(|_| entry.is_dir()) {
+        Some(sizes) => sizes.get(entry.path()).copied().unwrap_or(0),
+        None => entry.size(),
+    };
+    entries.sort_by(|a, b| {
+        let dir_cmp = if entries_first {
+            a.is_dir().cmp(&b.is_dir())
+        } else {
+            b.is_dir().cmp(&a.is_dir())
+        };
+        if dir_cmp != Ordering::Equal {
+            return dir_cmp;
+        }
+
+        let cmp = match key {
+            SortKey::Name => a
+                .name()
+                .to_string_lossy()
+                .to_lowercase()
+                .cmp(&b.name().to_string_lossy().to_lowercase()),
+            SortKey::Size => size(b).cmp(&size(a)),
+            SortKey::Age => b.modified().cmp(&a.modified()),
+        };
+
+        if reverse { cmp.reverse() } else { cmp }
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DisplayEntry {
+    entry: EntryInfo,
+    prefix: String,
+    #[serde(serialize_with = "serialize_path")]
+    rel_path: PathBuf,
+    entry_count: Option<u64>,
+    subtree_bytes: Option<u64>,
+    hardlink_id: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum EntryFilter {
+    Glob(GlobMatcher),
+    Fuzzy {
+        pattern: String,
+        ignore_case: bool,
+        threshold: i64,
+    },
+}
+
+impl EntryFilter {
+    fn is_match(&self, rel_path: &Path, name: &OsStr) -> bool {
+        match self {
+            EntryFilter::Glob(glob) => glob.is_match(normalize_match_path(rel_path)),
+            EntryFilter::Fuzzy {
+                pattern,
+                ignore_case,
+                threshold,
+            } => fuzzy_match_score(pattern, name, *ignore_case).is_some_and(|s| s >= *threshold),
+        }
+    }
+}
+
+fn skim_matcher(ignore_case: bool) -> SkimMatcherV2 {
+    if ignore_case {
+        SkimMatcherV2::default().ignore_case()
+    } else {
+        SkimMatcherV2::default()
+    }
+}
+
+fn fuzzy_match_score(pattern: &str, name: &OsStr, ignore_case: bool) -> Option<i64> {
+    skim_matcher(ignore_case).fuzzy_match(&name.to_string_lossy(), pattern)
+}
+
+fn fuzzy_score(entry: &EntryInfo, options: &ListOptions) -> Option<i64> {
+    let pattern = options.filter.as_deref().filter(|_| options.fuzzy)?;
+    fuzzy_match_score(pattern, &entry.name, options.ignore_case)
+}
+
+fn compile_filter(options: &ListOptions) -> Result<Option<EntryFilter>> {
+    let Some(pattern) = options.filter.as_deref() else {
+        return Ok(None);
+    };
+    if options.fuzzy {
+        return Ok(Some(EntryFilter::Fuzzy {
+            pattern: pattern.to_string(),
+            ignore_case: options.ignore_case,
+            threshold: options.fuzzy_threshold,
+        }));
+    }
+    let glob = GlobBuilder::new(pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid glob: {pattern}"))?;
+    Ok(Some(EntryFilter::Glob(glob.compile_matcher())))
+}
+
+fn build_display_entries_for_dir(
+    dir: &Path,
+    root: &Path,
+    options: &ListOptions,
+    matcher: Option<&EntryFilter>,
+) -> Result<Vec<DisplayEntry>> {
+    let dir_sizes = if options.sort_dirs_by_size && options.sort == SortKey::Size {
+        let mut sizes = HashMap::new();
+        collect_subtree_sizes(dir, root, options, matcher, &mut sizes)?;
+        Some(sizes)
+    } else {
+        None
+    };
+    let dir_sizes = dir_sizes.as_ref();
+
+    if options.tree {
+        let md = fs::symlink_metadata(dir)?;
+        let root_entry = EntryInfo {
+            name: dir
+                .file_name()
+                .map(OsString::from)
+                .unwrap_or_else(|| OsString::from(dir.as_os_str())),
+            path: dir.to_path_buf(),
+            file_type: md.file_type(),
+            modified: md.modified().ok(),
+            metadata: md,
+        };
+
+        let mut out = Vec::new();
+        let root_rel = PathBuf::from(".");
+        if !options.only_files {
+            out.push(DisplayEntry {
+                entry_count: tree_entry_count(&root_entry, options)?,
+                subtree_bytes: None,
+                hardlink_id: None,
+                entry: root_entry,
+                prefix: String::new(),
+                rel_path: root_rel,
+            });
+        }
+        let mut ancestor_more = Vec::new();
+        collect_tree_children(
+            dir,
+            root,
+            options,
+            matcher,
+            dir_sizes,
+            &mut ancestor_more,
+            &mut out,
+        )?;
+
+        if options.subtree_sizes {
+            let mut sizes = HashMap::new();
+            collect_subtree_sizes(dir, root, options, matcher, &mut sizes)?;
+            for display in out.iter_mut().filter(|e| e.entry.is_dir()) {
+                display.subtree_bytes = sizes.get(&display.entry.path).copied();
+            }
+        }
+        if options.reverse_tree {
+            // Postorder is the preorder tree mirrored vertically: the ancestor bars still line up,
+            // only each last sibling's corner has to open downwards towards its parent.
+            out.reverse();
+            for display in &mut out {
+                display.prefix = display.prefix.replace('└', "┌");
+            }
+        }
+        Ok(out)
+    } else {
+        let mut entries = timed(Phase::Read, || read_entries(dir, options))?;
+        timed(Phase::Sort, || {
+            sort_listing(&mut entries, options, dir_sizes)
+        });
+        let mut out = Vec::new();
+        for entry in entries {
+            let rel_path = entry
+                .path
+                .strip_prefix(root)
+                .unwrap_or(&entry.path)
+                .to_path_buf();
+
+            if !should_print_entry(&entry, &rel_path, options, matcher) {
+                continue;
+            }
+
+            out.push(DisplayEntry {
+                entry,
+                prefix: String::new(),
+                rel_path,
+                entry_count: None,
+                subtree_bytes: None,
+                hardlink_id: None,
+            });
+        }
+        if options.fuzzy && !options.no_sort {
+            out.sort_by_key(|e| std::cmp::Reverse(fuzzy_score(&e.entry, options)));
+        }
+        Ok(out)
+    }
+}
+
+fn collect_tree_children(
+    dir: &Path,
+    root: &Path,
+    options: &ListOptions,
+    matcher: Option<&EntryFilter>,
+    dir_sizes: Option<&HashMap<PathBuf, u64>>,
+    ancestor_more: &mut Vec<bool>,
+    out: &mut Vec<DisplayEntry>,
+) -> Result<bool> {
+    let mut entries = timed(Phase::Read, || read_entries(dir, options))?;
+    timed(Phase::Sort, || {
+        sort_listing(&mut entries, options, dir_sizes)
+    });
+
+    let mut printable: Vec<(EntryInfo, PathBuf, bool)> = Vec::new();
+    for entry in entries {
+        let rel_path = entry
+            .path
+            .strip_prefix(root)
+            .unwrap_or(&entry.path)
+            .to_path_buf();
+        let child_has = if entry.is_dir() {
+            subtree_has_printables(&entry.path, root, options, matcher)?
+        } else {
+            false
+        };
+        let direct = should_print_entry(&entry, &rel_path, options, matcher);
+        let context = entry.is_dir() && !options.only_files && child_has;
+        if direct || context {
+            printable.push((entry, rel_path, child_has));
+        }
+    }
+
+    let total = printable.len();
+    let mut any_printed = false;
+    for (idx, (entry, rel_path, _child_has)) in printable.into_iter().enumerate() {
+        let is_last = idx + 1 == total;
+        let prefix = if options.plain {
+            "  ".repeat(ancestor_more.len() + 1)
+        } else {
+            tree_prefix(ancestor_more, is_last, options.compact)
+        };
+        out.push(DisplayEntry {
+            entry: entry.clone(),
+            prefix,
+            rel_path: rel_path.clone(),
+            entry_count: tree_entry_count(&entry, options)?,
+            subtree_bytes: None,
+            hardlink_id: None,
+        });
+        any_printed = true;
+
+        if entry.is_dir() {
+            ancestor_more.push(!is_last);
+            collect_tree_children(
+                &entry.path,
+                root,
+                options,
+                matcher,
+                dir_sizes,
+                ancestor_more,
+                out,
+            )?;
+            ancestor_more.pop();
+        }
+    }
+
+    Ok(any_printed)
+}
+
+fn collect_subtree_sizes(
+    dir: &Path,
     root: &Path,
     options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
-    ancestor_more: &mut Vec<bool>,
-    out: &mut Vec<DisplayEntry>,
-) -> Result<bool> {
-    let mut entries = read_entries(dir, options.all)?;
-    sort_entries(&mut entries, options.sort, options.reverse);
+    matcher: Option<&EntryFilter>,
+    sizes: &mut HashMap<PathBuf, u64>,
+) -> Result<u64> {
+    let mut total = 0u64;
+    for entry in read_entries(dir, options)? {
+        if entry.is_dir() {
+            total += collect_subtree_sizes(&entry.path, root, options, matcher, sizes)?;
+            continue;
+        }
+        let rel_path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+        if matcher.is_none_or(|m| m.is_match(rel_path, &entry.name)) {
+            total += entry.size();
+        }
+    }
+    sizes.insert(dir.to_path_buf(), total);
+    Ok(total)
+}
 
-    let mut printable: Vec<(EntryInfo, PathBuf, bool)> = Vec::new();
+#[cfg(unix)]
+fn assign_hardlink_ids(entries: &mut [DisplayEntry]) {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut ids: HashMap<(u64, u64), usize> = HashMap::new();
+    for display in entries.iter_mut() {
+        let md = &display.entry.metadata;
+        if !md.is_file() || md.nlink() < 2 {
+            continue;
+        }
+        let next_id = ids.len() + 1;
+        let id = *ids.entry((md.dev(), md.ino())).or_insert(next_id);
+        display.hardlink_id = Some(id);
+    }
+}
+
+#[cfg(not(unix))]
+fn assign_hardlink_ids(_entries: &mut [DisplayEntry]) {}
+
+fn tree_entry_count(entry: &EntryInfo, options: &ListOptions) -> Result<Option<u64>> {
+    if !options.show_count || !entry.is_dir() {
+        return Ok(None);
+    }
+    Ok(Some(read_entries(&entry.path, options)?.len() as u64))
+}
+
+fn tree_prefix(ancestor_more: &[bool], is_last: bool, compact: bool) -> String {
+    let (pipe, blank, last, branch) = if compact {
+        ("│ ", "  ", "└ ", "├ ")
+    } else {
+        ("│   ", "    ", "└── ", "├── ")
+    };
+    let mut s = String::new();
+    for &more in ancestor_more {
+        if more {
+            s.push_str(pipe);
+        } else {
+            s.push_str(blank);
+        }
+    }
+    if is_last {
+        s.push_str(last);
+    } else {
+        s.push_str(branch);
+    }
+    s
+}
+
+fn subtree_has_printables(
+    dir: &Path,
+    root: &Path,
+    options: &ListOptions,
+    matcher: Option<&EntryFilter>,
+) -> Result<bool> {
+    let entries = timed(Phase::Read, || read_entries(dir, options))?;
     for entry in entries {
         let rel_path = entry
             .path
             .strip_prefix(root)
             .unwrap_or(&entry.path)
             .to_path_buf();
-        let child_has = if entry.is_dir() {
-            subtree_has_printables(&entry.path, root, options, matcher)?
+        if should_print_entry(&entry, &rel_path, options, matcher) {
+            return Ok(true);
+        }
+        if entry.is_dir() && subtree_has_printables(&entry.path, root, options, matcher)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn should_print_entry<E: ListingEntry>(
+    entry: &E,
+    rel_path: &Path,
+    options: &ListOptions,
+    matcher: Option<&EntryFilter>,
+) -> bool {
+    if options.only_dirs && !entry.is_dir() {
+        return false;
+    }
+    if options.only_files && entry.is_dir() {
+        return false;
+    }
+    if options.hidden_only && !is_hidden(entry.name()) {
+        return false;
+    }
+    if let Some(matcher) = matcher
+        && !matcher.is_match(rel_path, entry.name())
+    {
+        return false;
+    }
+    // Last, since it runs git once per file.
+    options.author.as_ref().is_none_or(|pattern| {
+        entry.is_dir() || author_matches(entry.path(), pattern, options.ignore_case)
+    })
+}
+
+// Filled lazily per file and cleared at the start of every listing, so --watch picks up new commits.
+static AUTHORS: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
+
+fn clear_author_cache() {
+    AUTHORS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+}
+
+// Substring match against "Name <email>" of the last commit touching the file; files outside a
+// repository or without history never match.
+fn author_matches(path: &Path, pattern: &str, ignore_case: bool) -> bool {
+    let mut authors = AUTHORS.lock().unwrap_or_else(PoisonError::into_inner);
+    let author = authors
+        .get_or_insert_with(HashMap::new)
+        .entry(path.to_path_buf())
+        .or_insert_with(|| last_commit_author(path).unwrap_or_default());
+    if author.is_empty() {
+        false
+    } else if ignore_case {
+        author.to_lowercase().contains(&pattern.to_lowercase())
+    } else {
+        author.contains(pattern)
+    }
+}
+
+fn last_commit_author(path: &Path) -> Option<String> {
+    let output = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(
+            path.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new(".")),
+        )
+        .args(["log", "-1", "--format=%an <%ae>", "--"])
+        .arg(path.file_name()?)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn normalize_match_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn print_long(entries: &[DisplayEntry], options: &ListOptions) -> Result<()> {
+    let mut mode_w = 0usize;
+    let mut size_w = 0usize;
+    let mut time_w = 0usize;
+    let mut kind_w = 0usize;
+    let mut score_w = 0usize;
+    let mut fs_w = 0usize;
+    let mut free_w = 0usize;
+
+    let mut rows = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let mode_raw = format_mode(&entry.entry);
+        let size_raw = format_size(entry.entry.size(), options.human);
+        let time_raw = entry
+            .entry
+            .modified
+            .map(|t| format_timestamp(t, options, true))
+            .unwrap_or_else(|| "-".to_string());
+        let kind_raw = if options.sniff {
+            entry.entry.content_type().unwrap_or("-").to_string()
         } else {
-            false
+            String::new()
         };
-        let direct = should_print_entry(&entry, &rel_path, options, matcher);
-        let context = entry.is_dir() && !options.only_files && child_has;
-        if direct || context {
-            printable.push((entry, rel_path, child_has));
+        let score_raw = if options.fuzzy {
+            fuzzy_score(&entry.entry, options).map_or_else(|| "-".to_string(), |s| s.to_string())
+        } else {
+            String::new()
+        };
+        let (fs_raw, free_raw) = if options.mount_info {
+            mount_info(&entry.entry)
+        } else {
+            Default::default()
+        };
+        let name = display_name(entry, options);
+
+        fs_w = fs_w.max(fs_raw.chars().count());
+        free_w = free_w.max(free_raw.chars().count());
+        score_w = score_w.max(score_raw.chars().count());
+        mode_w = mode_w.max(mode_raw.chars().count());
+        size_w = size_w.max(size_raw.chars().count());
+        time_w = time_w.max(time_raw.chars().count());
+        kind_w = kind_w.max(kind_raw.chars().count());
+
+        rows.push((
+            mode_raw, size_raw, time_raw, kind_raw, fs_raw, free_raw, score_raw, name,
+        ));
+    }
+
+    let term_width = crossterm::terminal::size()
+        .map(|(w, _)| w as usize)
+        .unwrap_or(80);
+
+    for (entry, (mode_raw, size_raw, time_raw, kind_raw, fs_raw, free_raw, score_raw, name)) in
+        entries.iter().zip(rows)
+    {
+        // Pad the plain text before painting; escape codes would count towards the width.
+        let mode = paint(format!("{mode_raw:>mode_w$}"), Style::new().bright_yellow());
+        let size = paint(
+            format!("{size_raw:>size_w$}"),
+            Style::new().bright_magenta(),
+        );
+        let time = paint(format!("{time_raw:>time_w$}"), Style::new().bright_black());
+        let score = if options.fuzzy {
+            format!(
+                "{}  ",
+                paint(
+                    format!("{score_raw:>score_w$}"),
+                    Style::new().bright_green()
+                )
+            )
+        } else {
+            String::new()
+        };
+        let kind = if options.sniff {
+            format!(
+                "{}  ",
+                paint(format!("{kind_raw:<kind_w$}"), Style::new().bright_cyan())
+            )
+        } else {
+            String::new()
+        };
+        let mount = if options.mount_info {
+            format!(
+                "{}  {}  ",
+                paint(format!("{fs_raw:<fs_w$}"), Style::new().bright_cyan()),
+                paint(
+                    format!("{free_raw:>free_w$}"),
+                    Style::new().bright_magenta()
+                )
+            )
+        } else {
+            String::new()
+        };
+        outln!("{mode}  {size}  {time}  {kind}{mount}{score}{name}");
+
+        if let Some(lines) = options.preview.filter(|&n| n > 0) {
+            let indent = format!("{}    ", entry.prefix);
+            let width = term_width.saturating_sub(visible_width(&indent)).max(1);
+            for line in preview_lines(&entry.entry, lines) {
+                let line: String = line.chars().take(width).collect();
+                outln!("{indent}{}", paint(line, Style::new().bright_black()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Binary files (known magic bytes, or a NUL byte in the first block) get no preview.
+fn preview_lines(entry: &EntryInfo, max_lines: usize) -> Vec<String> {
+    if !entry.file_type.is_file() || entry.content_type().is_some_and(|kind| kind != "script") {
+        return Vec::new();
+    }
+    let Ok(file) = fs::File::open(&entry.path) else {
+        return Vec::new();
+    };
+    let mut head = Vec::new();
+    if file.take(64 * 1024).read_to_end(&mut head).is_err() || head.contains(&0) {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&head)
+        .lines()
+        .take(max_lines)
+        .map(|line| {
+            line.replace('\t', "    ")
+                .chars()
+                .filter(|c| !c.is_control())
+                .collect()
+        })
+        .collect()
+}
+
+fn print_wide(entries: &[DisplayEntry], options: &ListOptions) {
+    print_wide_rows(
+        entries.iter().map(|entry| {
+            let name = display_name(entry, options);
+            (name, entry.entry.size(), entry.entry.modified)
+        }),
+        options,
+    );
+}
+
+fn print_wide_rows(
+    rows: impl Iterator<Item = (String, u64, Option<SystemTime>)>,
+    options: &ListOptions,
+) {
+    const NAME_W: usize = 40;
+    const SIZE_W: usize = 10;
+    const TIME_W: usize = 20;
+
+    let term_width = crossterm::terminal::size()
+        .map(|(w, _)| w as usize)
+        .unwrap_or(80);
+    let show_time = term_width >= NAME_W + SIZE_W + TIME_W + 4;
+
+    for (name, size, modified) in rows {
+        let pad = NAME_W.saturating_sub(name_width(&name, options)).max(1);
+        let size = format_size(size, options.human);
+        let mut line = format!(
+            "{name}{:pad$}{}",
+            "",
+            paint(format!("{size:>SIZE_W$}"), Style::new().bright_magenta())
+        );
+        if show_time {
+            let time = modified
+                .map(|t| format_timestamp(t, options, false))
+                .unwrap_or_else(|| "-".to_string());
+            line.push_str(&format!("  {}", paint(time, Style::new().bright_black())));
+        }
+        outln!("{line}");
+    }
+}
+
+// Without --timestamp-precision, `precise` call sites keep humantime's default sub-second output.
+fn format_timestamp(time: SystemTime, options: &ListOptions, precise: bool) -> String {
+    if options.timestamp_local && !options.timestamp_utc {
+        let format = match options.timestamp_precision {
+            None | Some(TimePrecision::Seconds) => "%Y-%m-%d %H:%M:%S%:z",
+            Some(TimePrecision::Millis) => "%Y-%m-%d %H:%M:%S%.3f%:z",
+            Some(TimePrecision::Nanos) => "%Y-%m-%d %H:%M:%S%.9f%:z",
+        };
+        return DateTime::<Local>::from(time).format(format).to_string();
+    }
+    match options.timestamp_precision {
+        Some(TimePrecision::Seconds) => humantime::format_rfc3339_seconds(time).to_string(),
+        Some(TimePrecision::Millis) => humantime::format_rfc3339_millis(time).to_string(),
+        Some(TimePrecision::Nanos) => humantime::format_rfc3339_nanos(time).to_string(),
+        None if precise => humantime::format_rfc3339(time).to_string(),
+        None => humantime::format_rfc3339_seconds(time).to_string(),
+    }
+}
+
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+// `visible_width` counts every char as one column; an icon emoji takes `--emoji-width` columns.
+fn name_width(name: &str, options: &ListOptions) -> usize {
+    let icon_extra = if options.icons && !options.plain {
+        usize::from(options.emoji_width) - 1
+    } else {
+        0
+    };
+    visible_width(name) + icon_extra
+}
+
+fn format_mode<E: ListingEntry>(entry: &E) -> String {
+    let type_char = if entry.is_dir() {
+        'd'
+    } else if entry.is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+
+    let writable = if entry.is_readonly() { '-' } else { 'w' };
+
+    format!("{type_char}r{writable}")
+}
+
+fn format_size(size: u64, human: bool) -> String {
+    if !human {
+        return format_integer_with_separators(size, thousands_separator());
+    }
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut f = size as f64;
+    let mut idx = 0usize;
+    while f >= 1024.0 && idx + 1 < UNITS.len() {
+        f /= 1024.0;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{size} {}", UNITS[idx])
+    } else {
+        format!("{:.1} {}", f, UNITS[idx])
+    }
+}
+
+fn format_integer_with_separators(n: u64, sep: char) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (idx, c) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn thousands_separator() -> char {
+    static SEPARATOR: OnceLock<char> = OnceLock::new();
+    *SEPARATOR.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let lang = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match lang.as_str() {
+            "de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "sl" | "hr" => {
+                '.'
+            }
+            _ => ',',
+        }
+    })
+}
+
+fn display_name(entry: &DisplayEntry, options: &ListOptions) -> String {
+    let prefix = if entry.prefix.is_empty() {
+        String::new()
+    } else {
+        paint(&entry.prefix, Style::new().bright_black())
+    };
+    let count = match entry.entry_count {
+        Some(1) => format!(" {}", paint("(1 item)", Style::new().bright_black())),
+        Some(n) => format!(
+            " {}",
+            paint(format!("({n} items)"), Style::new().bright_black())
+        ),
+        None => String::new(),
+    };
+    let subtree = match entry.subtree_bytes {
+        Some(bytes) => format!(
+            "  {}",
+            paint(
+                format!("({})", format_size(bytes, true)),
+                Style::new().bright_black()
+            )
+        ),
+        None => String::new(),
+    };
+    let hardlink = match entry.hardlink_id {
+        Some(id) => format!(" {}", paint(format!("[H{id}]"), Style::new().bright_red())),
+        None => String::new(),
+    };
+    let sparse =
+        match allocated_size(&entry.entry).filter(|_| options.sparse && is_sparse(&entry.entry)) {
+            Some(allocated) => format!(
+                " {} {}",
+                paint("[sparse]", Style::new().bright_blue()),
+                paint(
+                    format!(
+                        "({} apparent, {} allocated)",
+                        format_size(entry.entry.size(), options.human),
+                        format_size(allocated, options.human)
+                    ),
+                    Style::new().bright_black()
+                )
+            ),
+            None => String::new(),
+        };
+    format!(
+        "{prefix}{}{count}{subtree}{hardlink}{sparse}",
+        format_name(&entry.entry, &entry.rel_path, options)
+    )
+}
+
+#[cfg(unix)]
+fn allocated_size(entry: &EntryInfo) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    // `st_blocks` is always counted in 512-byte units, whatever the filesystem block size.
+    entry
+        .file_type
+        .is_file()
+        .then(|| entry.metadata.blocks() * 512)
+}
+
+#[cfg(not(unix))]
+fn allocated_size(_entry: &EntryInfo) -> Option<u64> {
+    None
+}
+
+fn is_sparse(entry: &EntryInfo) -> bool {
+    allocated_size(entry).is_some_and(|allocated| allocated < entry.size())
+}
+
+fn format_name<E: ListingEntry>(entry: &E, rel_path: &Path, options: &ListOptions) -> String {
+    let name = entry.name().to_string_lossy();
+    if options.plain {
+        return name.into_owned();
+    }
+    let icon = if options.icons {
+        let icon = if entry.is_dir() {
+            "📁"
+        } else if entry.is_symlink() && !options.no_symlink_icon {
+            "🔗"
+        } else if let Some(kind) = options.sniff.then(|| entry.content_type()).flatten() {
+            content_type_icon(kind)
+        } else {
+            "📄"
+        };
+        format!("{icon}{:1$}", "", options.icons_padding)
+    } else {
+        String::new()
+    };
+
+    let suffix = if entry.is_dir() {
+        std::path::MAIN_SEPARATOR.to_string()
+    } else {
+        String::new()
+    };
+
+    let style = if options.rainbow {
+        let (r, g, b) = rainbow_rgb(rel_path);
+        rgb_style(r, g, b)
+    } else {
+        options.colors.for_entry(entry).style()
+    };
+
+    let highlight = highlight_regex(options)
+        .ok()
+        .flatten()
+        .and_then(|re| re.find(name.as_ref()))
+        .filter(|m| !m.is_empty());
+    if let Some(m) = highlight {
+        let segment = |text: String, style: Style| {
+            if text.is_empty() {
+                text
+            } else {
+                paint(text, style)
+            }
+        };
+        return format!(
+            "{}{}{}",
+            segment(format!("{icon}{}", &name[..m.start()]), style),
+            paint(&name[m.range()], Style::new().bold().bright_yellow()),
+            segment(format!("{}{suffix}", &name[m.end()..]), style)
+        );
+    }
+
+    paint(format!("{icon}{name}{suffix}"), style)
+}
+
+// Compiled once per pattern; `run` calls this up front so an invalid pattern fails early.
+fn highlight_regex(options: &ListOptions) -> Result<Option<MetaRegex>> {
+    static CACHE: Mutex<Option<(String, MetaRegex)>> = Mutex::new(None);
+
+    let Some(pattern) = options.highlight_pattern.as_deref() else {
+        return Ok(None);
+    };
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((cached, re)) = cache.as_ref()
+        && cached == pattern
+    {
+        return Ok(Some(re.clone()));
+    }
+    let mut source = glob_to_regex(pattern);
+    if options.ignore_case {
+        source.insert_str(0, "(?i)");
+    }
+    let re =
+        MetaRegex::new(&source).with_context(|| format!("Invalid highlight pattern: {pattern}"))?;
+    *cache = Some((pattern.to_string(), re.clone()));
+    Ok(Some(re))
+}
+
+// Supports `*` and `?`; leading and trailing `*` are dropped since the search is unanchored.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::new();
+    for c in glob.trim_matches('*').chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c if "\\.+()|[]{}^$#&-~".contains(c) => {
+                re.push('\\');
+                re.push(c);
+            }
+            c => re.push(c),
+        }
+    }
+    re
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorSpec {
+    Named(BaseColor, bool),
+    Rgb(u8, u8, u8),
+}
+
+impl ColorSpec {
+    fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Some(hex) = value.strip_prefix('#') {
+            anyhow::ensure!(
+                hex.len() == 6 && hex.is_ascii(),
+                "Invalid hex color: {value}"
+            );
+            let channel = |i: usize| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .with_context(|| format!("Invalid hex color: {value}"))
+            };
+            return Ok(ColorSpec::Rgb(channel(0)?, channel(2)?, channel(4)?));
         }
+
+        let lower = value.to_ascii_lowercase();
+        let (name, bright) = match lower.strip_prefix("bright_") {
+            Some(name) => (name, true),
+            None => (lower.as_str(), false),
+        };
+        let base = match name {
+            "black" => BaseColor::Black,
+            "red" => BaseColor::Red,
+            "green" => BaseColor::Green,
+            "yellow" => BaseColor::Yellow,
+            "blue" => BaseColor::Blue,
+            "magenta" => BaseColor::Magenta,
+            "cyan" => BaseColor::Cyan,
+            "white" => BaseColor::White,
+            _ => anyhow::bail!("Unknown color: {value}"),
+        };
+        Ok(ColorSpec::Named(base, bright))
     }
 
-    let total = printable.len();
-    let mut any_printed = false;
-    for (idx, (entry, rel_path, _child_has)) in printable.into_iter().enumerate() {
-        let is_last = idx + 1 == total;
-        let prefix = tree_prefix(ancestor_more, is_last);
-        out.push(DisplayEntry {
-            entry: entry.clone(),
-            prefix,
-            rel_path: rel_path.clone(),
-        });
-        any_printed = true;
+    fn style(self) -> Style {
+        match self {
+            ColorSpec::Rgb(r, g, b) => rgb_style(r, g, b),
+            ColorSpec::Named(base, bright) => {
+                let ansi = match (base, bright) {
+                    (BaseColor::Black, false) => AnsiColors::Black,
+                    (BaseColor::Red, false) => AnsiColors::Red,
+                    (BaseColor::Green, false) => AnsiColors::Green,
+                    (BaseColor::Yellow, false) => AnsiColors::Yellow,
+                    (BaseColor::Blue, false) => AnsiColors::Blue,
+                    (BaseColor::Magenta, false) => AnsiColors::Magenta,
+                    (BaseColor::Cyan, false) => AnsiColors::Cyan,
+                    (BaseColor::White, false) => AnsiColors::White,
+                    (BaseColor::Black, true) => AnsiColors::BrightBlack,
+                    (BaseColor::Red, true) => AnsiColors::BrightRed,
+                    (BaseColor::Green, true) => AnsiColors::BrightGreen,
+                    (BaseColor::Yellow, true) => AnsiColors::BrightYellow,
+                    (BaseColor::Blue, true) => AnsiColors::BrightBlue,
+                    (BaseColor::Magenta, true) => AnsiColors::BrightMagenta,
+                    (BaseColor::Cyan, true) => AnsiColors::BrightCyan,
+                    (BaseColor::White, true) => AnsiColors::BrightWhite,
+                };
+                Style::new().color(ansi)
+            }
+        }
+    }
 
-        if entry.is_dir() {
-            ancestor_more.push(!is_last);
-            collect_tree_children(&entry.path, root, options, matcher, ancestor_more, out)?;
-            ancestor_more.pop();
+    fn tui_color(self) -> Color {
+        match self {
+            ColorSpec::Rgb(r, g, b) if COLOR_256.load(AtomicOrdering::Relaxed) => {
+                Color::from_256colors(nearest_256(r, g, b))
+            }
+            ColorSpec::Rgb(r, g, b) => Color::Rgb(r, g, b),
+            ColorSpec::Named(base, true) => Color::Light(base),
+            ColorSpec::Named(base, false) => Color::Dark(base),
         }
     }
+}
 
-    Ok(any_printed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ColorConfig {
+    dir: ColorSpec,
+    symlink: ColorSpec,
+    executable: ColorSpec,
+    file: ColorSpec,
 }
 
-fn tree_prefix(ancestor_more: &[bool], is_last: bool) -> String {
-    let mut s = String::new();
-    for &more in ancestor_more {
-        if more {
-            s.push_str("│   ");
-        } else {
-            s.push_str("    ");
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            dir: ColorSpec::Named(BaseColor::Blue, true),
+            symlink: ColorSpec::Named(BaseColor::Cyan, true),
+            executable: ColorSpec::Named(BaseColor::Green, true),
+            file: ColorSpec::Named(BaseColor::White, true),
         }
     }
-    if is_last {
-        s.push_str("└── ");
-    } else {
-        s.push_str("├── ");
-    }
-    s
 }
 
-fn subtree_has_printables(
-    dir: &Path,
-    root: &Path,
-    options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
-) -> Result<bool> {
-    let entries = read_entries(dir, options.all)?;
-    for entry in entries {
-        let rel_path = entry
-            .path
-            .strip_prefix(root)
-            .unwrap_or(&entry.path)
-            .to_path_buf();
-        if should_print_entry(&entry, &rel_path, options, matcher) {
-            return Ok(true);
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColorConfigFile {
+    dir: Option<String>,
+    symlink: Option<String>,
+    executable: Option<String>,
+    file: Option<String>,
+}
+
+impl ColorConfig {
+    fn load() -> Result<Self> {
+        let Some(path) = config_dir().map(|dir| dir.join("colors.toml")) else {
+            return Ok(Self::default());
+        };
+        if !path.is_file() {
+            return Ok(Self::default());
         }
-        if entry.is_dir() && subtree_has_printables(&entry.path, root, options, matcher)? {
-            return Ok(true);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid color config {}", path.display()))
+    }
+
+    fn from_toml(text: &str) -> Result<Self> {
+        let file: ColorConfigFile = toml::from_str(text)?;
+        let mut config = Self::default();
+        for (slot, value) in [
+            (&mut config.dir, file.dir),
+            (&mut config.symlink, file.symlink),
+            (&mut config.executable, file.executable),
+            (&mut config.file, file.file),
+        ] {
+            if let Some(value) = value {
+                *slot = ColorSpec::parse(&value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    fn for_entry<E: ListingEntry>(&self, entry: &E) -> ColorSpec {
+        if entry.is_dir() {
+            self.dir
+        } else if entry.is_symlink() {
+            self.symlink
+        } else if is_probably_executable(entry.path()) {
+            self.executable
+        } else {
+            self.file
         }
     }
-    Ok(false)
 }
 
-fn should_print_entry(
-    entry: &EntryInfo,
-    rel_path: &Path,
-    options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
-) -> bool {
-    if options.only_dirs && !entry.is_dir() {
-        return false;
+fn config_dir() -> Option<PathBuf> {
+    if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(xdg).join("lz"));
     }
-    if options.only_files && entry.is_dir() {
-        return false;
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".config").join("lz"))
+}
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+static COLOR_256: AtomicBool = AtomicBool::new(false);
+
+fn rgb_style(r: u8, g: u8, b: u8) -> Style {
+    if COLOR_256.load(AtomicOrdering::Relaxed) {
+        Style::new().color(XtermColors::from(nearest_256(r, g, b)))
+    } else {
+        Style::new().truecolor(r, g, b)
     }
+}
 
-    let Some(matcher) = matcher else {
-        return true;
+// Nearest entry of the xterm 6x6x6 color cube (16..=231) or the grayscale ramp (232..=255).
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let cube_index = |v: u8| match v {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (v - 35) / 40,
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r, r2) + d(g, g2) + d(b, b2)
     };
-    matcher.is_match(normalize_match_path(rel_path))
+
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = (
+        LEVELS[ri as usize],
+        LEVELS[gi as usize],
+        LEVELS[bi as usize],
+    );
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+
+    if distance((gray_level, gray_level, gray_level)) < distance(cube) {
+        232 + gray_index
+    } else {
+        16 + 36 * ri + 6 * gi + bi
+    }
 }
 
-fn normalize_match_path(path: &Path) -> String {
-    path.to_string_lossy().replace('\\', "/")
+enum OutputSink {
+    Stdout,
+    Buffer(Vec<u8>),
+    Pager(Child),
 }
 
-fn print_long(entries: &[DisplayEntry], options: &ListOptions) -> Result<()> {
-    let mut mode_w = 0usize;
-    let mut size_w = 0usize;
-    let mut time_w = 0usize;
+impl OutputSink {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            OutputSink::Stdout => io::stdout().write_all(bytes),
+            OutputSink::Buffer(buf) => buf.write_all(bytes),
+            OutputSink::Pager(child) => match child.stdin.as_mut() {
+                Some(stdin) => stdin.write_all(bytes),
+                None => Ok(()),
+            },
+        }
+    }
+}
 
-    let mut rows = Vec::with_capacity(entries.len());
-    for entry in entries {
-        let mode_raw = format_mode(&entry.entry);
-        let size_raw = format_size(entry.entry.size(), options.human);
-        let time_raw = entry
-            .entry
-            .modified
-            .map(humantime::format_rfc3339)
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "-".to_string());
+struct Output {
+    sink: OutputSink,
+    no_newline: bool,
+    strip_ansi: bool,
+    pending_newline: bool,
+}
 
-        let prefix = if entry.prefix.is_empty() {
-            String::new()
-        } else {
-            format!("{}", entry.prefix.bright_black())
-        };
-        let name = format!(
-            "{prefix}{}",
-            format_name(&entry.entry, &entry.rel_path, options)
-        );
+static OUTPUT: Mutex<Output> = Mutex::new(Output {
+    sink: OutputSink::Stdout,
+    no_newline: false,
+    strip_ansi: false,
+    pending_newline: false,
+});
+
+fn output() -> MutexGuard<'static, Output> {
+    OUTPUT.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-        mode_w = mode_w.max(mode_raw.len());
-        size_w = size_w.max(size_raw.len());
-        time_w = time_w.max(time_raw.len());
+fn set_output(sink: OutputSink) {
+    output().sink = sink;
+}
 
-        let mode = format!("{}", mode_raw.bright_yellow());
-        let size = format!("{}", size_raw.bright_magenta());
-        let time = format!("{}", time_raw.bright_black());
-        rows.push((mode_raw, mode, size_raw, size, time_raw, time, name));
+// Write errors (e.g. the pager quitting early) are ignored rather than panicking like println!.
+fn write_output(args: fmt::Arguments) {
+    let mut out = output();
+    let mut text = args.to_string();
+    if out.strip_ansi {
+        text = strip_ansi(&text);
     }
+    if out.no_newline {
+        // Hold back each line's newline until more output follows, so the last one can be dropped.
+        if out.pending_newline {
+            text.insert(0, '\n');
+        }
+        out.pending_newline = text.ends_with('\n');
+        if out.pending_newline {
+            text.pop();
+        }
+    }
+    let _ = out.sink.write_all(text.as_bytes());
+}
 
-    for (mode_raw, mode, size_raw, size, time_raw, time, name) in rows {
-        println!(
-            "{mode:>mode_w$}  {size:>size_w$}  {time:>time_w$}  {name}",
-            mode_w = mode_w,
-            size_w = size_w,
-            time_w = time_w
-        );
-        let _ = (&mode_raw, &size_raw, &time_raw);
+// Drops CSI sequences (colors, cursor movement), OSC sequences (titles, hyperlinks) and other
+// two-byte escapes.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
     }
+    out
+}
+
+fn spawn_pager() -> Result<Child> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    ProcessCommand::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start pager `{pager}`"))
+}
 
+fn finish_output() -> Result<()> {
+    let mut out = output();
+    out.pending_newline = false;
+    let sink = std::mem::replace(&mut out.sink, OutputSink::Stdout);
+    drop(out);
+    match sink {
+        OutputSink::Stdout => io::stdout().flush()?,
+        OutputSink::Buffer(buf) => {
+            let lines = buf.iter().filter(|&&b| b == b'\n').count();
+            let rows = crossterm::terminal::size()
+                .map(|(_, h)| h as usize)
+                .unwrap_or(usize::MAX);
+            if lines >= rows {
+                let mut child = spawn_pager()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(&buf);
+                }
+                child.wait()?;
+            } else {
+                let _ = io::stdout().write_all(&buf);
+            }
+        }
+        OutputSink::Pager(mut child) => {
+            drop(child.stdin.take());
+            child.wait()?;
+        }
+    }
     Ok(())
 }
 
-fn format_mode(entry: &EntryInfo) -> String {
-    let type_char = if entry.is_dir() {
-        'd'
-    } else if entry.is_symlink() {
-        'l'
-    } else {
-        '-'
-    };
+static ERROR_OUTPUT: Mutex<Option<fs::File>> = Mutex::new(None);
 
-    let writable = if entry.metadata.permissions().readonly() {
-        '-'
-    } else {
-        'w'
-    };
+fn report_error(err: &anyhow::Error) {
+    let mut file = ERROR_OUTPUT.lock().unwrap_or_else(PoisonError::into_inner);
+    match file.as_mut() {
+        Some(file) => {
+            let _ = writeln!(file, "{err:#}");
+        }
+        None => eprintln!("{}", paint(format!("{err:#}"), Style::new().bright_red())),
+    }
+}
 
-    format!("{type_char}r{writable}")
+struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    writer: io::BufWriter<fs::File>,
 }
 
-fn format_size(size: u64, human: bool) -> String {
-    if !human {
-        return size.to_string();
-    }
-    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
-    let mut f = size as f64;
-    let mut idx = 0usize;
-    while f >= 1024.0 && idx + 1 < UNITS.len() {
-        f /= 1024.0;
-        idx += 1;
+impl LogFile {
+    fn open(path: &Path, max_size: u64) -> Result<Self> {
+        // Keep a single previous generation: FILE is renamed to FILE.1, replacing any older one.
+        if fs::metadata(path).is_ok_and(|md| md.len() > max_size) {
+            let mut rotated = path.as_os_str().to_owned();
+            rotated.push(".1");
+            fs::rename(path, &rotated)
+                .with_context(|| format!("Failed to rotate log {}", path.display()))?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log {}", path.display()))?;
+        Ok(LogFile {
+            path: path.to_path_buf(),
+            max_size,
+            writer: io::BufWriter::new(file),
+        })
     }
-    if idx == 0 {
-        format!("{size} {}", UNITS[idx])
-    } else {
-        format!("{:.1} {}", f, UNITS[idx])
+
+    fn is_full(&self) -> bool {
+        self.writer
+            .get_ref()
+            .metadata()
+            .is_ok_and(|md| md.len() > self.max_size)
     }
 }
 
-fn format_name(entry: &EntryInfo, rel_path: &Path, options: &ListOptions) -> String {
-    let name = entry.name.to_string_lossy();
-    let icon = if options.icons {
-        if entry.is_dir() {
-            "📁 "
-        } else if entry.is_symlink() {
-            "🔗 "
-        } else {
-            "📄 "
-        }
-    } else {
-        ""
-    };
+static LOG: Mutex<Option<LogFile>> = Mutex::new(None);
 
-    let suffix = if entry.is_dir() {
-        std::path::MAIN_SEPARATOR.to_string()
-    } else {
-        String::new()
+fn open_log(path: &Path, max_size: u64) -> Result<()> {
+    *LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(LogFile::open(path, max_size)?);
+    Ok(())
+}
+
+// Each record is flushed as one write, so the log stays line-complete if lz is interrupted
+// (e.g. watch mode). The size is checked on every record so long --watch runs rotate too.
+fn log_operation(op: &str, path: &Path, entries: Option<usize>) {
+    let mut log = LOG.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(current) = log.as_mut() else {
+        return;
+    };
+    if current.is_full()
+        && let Ok(reopened) = LogFile::open(&current.path, current.max_size)
+    {
+        *current = reopened;
+    }
+    let writer = &mut current.writer;
+    let record = LogRecord {
+        time: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        op,
+        path: path.display().to_string(),
+        entries,
+    };
+    let Ok(line) = serde_json::to_string(&record) else {
+        return;
     };
+    let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+}
 
-    let full = format!("{icon}{name}{suffix}");
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    time: String,
+    op: &'a str,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entries: Option<usize>,
+}
 
-    if options.rainbow {
-        let (r, g, b) = rainbow_rgb(rel_path);
-        return format!("{}", full.truecolor(r, g, b));
-    }
+fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (digits, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, ""),
+    };
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("unknown size unit: {unit}")),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size: {value}"))
+}
 
-    if entry.is_dir() {
-        format!("{}", full.bright_blue())
-    } else if entry.is_symlink() {
-        format!("{}", full.bright_cyan())
-    } else if is_probably_executable(&entry.path) {
-        format!("{}", full.bright_green())
+fn paint(text: impl Display, style: Style) -> String {
+    if COLOR_ENABLED.load(AtomicOrdering::Relaxed) {
+        text.style(style).to_string()
     } else {
-        format!("{}", full.bright_white())
+        text.to_string()
     }
 }
 
@@ -698,7 +4241,7 @@ fn rainbow_rgb(path: &Path) -> (u8, u8, u8) {
     (r, g, b)
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct ListingSummary {
     total_bytes: u64,
     total_files: u64,
@@ -706,7 +4249,7 @@ struct ListingSummary {
     ext: BTreeMap<String, ExtSummary>,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct ExtSummary {
     files: u64,
     bytes: u64,
@@ -715,7 +4258,7 @@ struct ExtSummary {
 fn compute_summary(
     path: &Path,
     options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
+    matcher: Option<&EntryFilter>,
 ) -> Result<ListingSummary> {
     let metadata = fs::symlink_metadata(path)?;
     if metadata.is_file() {
@@ -745,17 +4288,32 @@ fn compute_summary(
 
     let mut summary = ListingSummary::default();
     walk_summary_dir(path, path, options, matcher, &mut summary)?;
+    if options.real_time_summary && io::stderr().is_tty() {
+        let _ = io::stderr().execute(Clear(ClearType::CurrentLine));
+        eprint!("\r");
+    }
     Ok(summary)
 }
 
+// The summary being built is the running total, so every walk starts again from zero.
+fn report_running_total(summary: &ListingSummary, options: &ListOptions) {
+    if summary.total_files.is_multiple_of(100) && io::stderr().is_tty() {
+        eprint!(
+            "\r{} in {} files...",
+            format_size(summary.total_bytes, options.human),
+            summary.total_files
+        );
+    }
+}
+
 fn walk_summary_dir(
     dir: &Path,
     root: &Path,
     options: &ListOptions,
-    matcher: Option<&GlobMatcher>,
+    matcher: Option<&EntryFilter>,
     summary: &mut ListingSummary,
 ) -> Result<()> {
-    let entries = read_entries(dir, options.all)?;
+    let entries = read_entries(dir, options)?;
     for entry in entries {
         let rel_path = entry
             .path
@@ -772,6 +4330,9 @@ fn walk_summary_dir(
             summary.total_files += 1;
             summary.total_bytes += entry.size();
             add_extension_stat(summary, &entry);
+            if options.real_time_summary {
+                report_running_total(summary, options);
+            }
         }
     }
     Ok(())
@@ -792,15 +4353,19 @@ fn add_extension_stat(summary: &mut ListingSummary, entry: &EntryInfo) {
     s.bytes += entry.size();
 }
 
-#[derive(Debug, Serialize)]
+const JSON_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
 struct JsonOutput {
+    version: u32,
+    refresh_id: Option<u64>,
     root: String,
     entries: Vec<JsonEntry>,
     summary: Option<JsonSummary>,
     error: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct JsonEntry {
     rel_path: String,
     name: String,
@@ -808,46 +4373,195 @@ struct JsonEntry {
     size: u64,
     modified: Option<String>,
     depth: usize,
+    entry_count: Option<u64>,
+    subtree_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JsonEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonSummary {
+    total_bytes: u64,
+    total_files: u64,
+    total_dirs: u64,
+    extensions: Option<BTreeMap<String, ExtSummary>>,
+}
+
+impl DisplayEntry {
+    fn to_json(&self) -> JsonEntry {
+        let kind = self.entry.kind();
+        let rel = normalize_match_path(&self.rel_path);
+        let name = self.entry.name.to_string_lossy().to_string();
+        let depth = if self.rel_path == Path::new(".") {
+            0
+        } else {
+            self.rel_path.components().count().saturating_sub(1)
+        };
+        let modified = self
+            .entry
+            .modified
+            .map(humantime::format_rfc3339)
+            .map(|s| s.to_string());
+        JsonEntry {
+            rel_path: rel,
+            name,
+            kind: kind.to_string(),
+            size: self.entry.size(),
+            modified,
+            depth,
+            entry_count: self.entry_count,
+            subtree_bytes: self.subtree_bytes,
+            children: Vec::new(),
+        }
+    }
+}
+
+fn output_entries_toml(out: &JsonOutput) -> Result<String> {
+    toml::to_string(out).context("Failed to serialize listing as TOML")
+}
+
+fn output_entries_yaml(out: &JsonOutput, flow: bool) -> Result<String> {
+    if flow {
+        let value = serde_yaml::to_value(out)?;
+        Ok(yaml_flow(&value))
+    } else {
+        let yaml = serde_yaml::to_string(out).context("Failed to serialize listing as YAML")?;
+        Ok(yaml.trim_end().to_string())
+    }
+}
+
+// serde_yaml only emits block style, so flow style is rendered by hand. String values are always
+// double-quoted (JSON string syntax is valid YAML) to sidestep plain-scalar ambiguities.
+fn yaml_flow(value: &serde_yaml::Value) -> String {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => serde_json::to_string(s).unwrap_or_default(),
+        Value::Sequence(items) => {
+            let items: Vec<_> = items.iter().map(yaml_flow).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Mapping(map) => {
+            let fields: Vec<_> = map
+                .iter()
+                .map(|(k, v)| {
+                    let key = match k.as_str() {
+                        Some(key) if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                            key.to_string()
+                        }
+                        _ => yaml_flow(k),
+                    };
+                    format!("{key}: {}", yaml_flow(v))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        Value::Tagged(tagged) => yaml_flow(&tagged.value),
+    }
+}
+
+fn output_entries_xml(out: &JsonOutput) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(XmlEvent::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    let mut listing = BytesStart::new("listing");
+    listing.push_attribute(("version", out.version.to_string().as_str()));
+    if let Some(id) = out.refresh_id {
+        listing.push_attribute(("refresh_id", id.to_string().as_str()));
+    }
+    listing.push_attribute(("root", out.root.as_str()));
+    writer.write_event(XmlEvent::Start(listing))?;
+
+    for entry in &out.entries {
+        write_xml_entry(&mut writer, entry)?;
+    }
+
+    if let Some(summary) = &out.summary {
+        let mut start = BytesStart::new("summary");
+        start.push_attribute(("total_bytes", summary.total_bytes.to_string().as_str()));
+        start.push_attribute(("total_files", summary.total_files.to_string().as_str()));
+        start.push_attribute(("total_dirs", summary.total_dirs.to_string().as_str()));
+        match &summary.extensions {
+            Some(exts) if !exts.is_empty() => {
+                writer.write_event(XmlEvent::Start(start))?;
+                for (ext, stats) in exts {
+                    let mut item = BytesStart::new("extension");
+                    item.push_attribute(("name", ext.as_str()));
+                    item.push_attribute(("files", stats.files.to_string().as_str()));
+                    item.push_attribute(("bytes", stats.bytes.to_string().as_str()));
+                    writer.write_event(XmlEvent::Empty(item))?;
+                }
+                writer.write_event(XmlEvent::End(BytesEnd::new("summary")))?;
+            }
+            _ => writer.write_event(XmlEvent::Empty(start))?,
+        }
+    }
+
+    writer.write_event(XmlEvent::End(BytesEnd::new("listing")))?;
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn write_xml_entry(writer: &mut Writer<Vec<u8>>, entry: &JsonEntry) -> Result<()> {
+    let mut start = BytesStart::new("entry");
+    start.push_attribute(("rel_path", entry.rel_path.as_str()));
+    start.push_attribute(("name", entry.name.as_str()));
+    start.push_attribute(("kind", entry.kind.as_str()));
+    start.push_attribute(("size", entry.size.to_string().as_str()));
+    if let Some(modified) = &entry.modified {
+        start.push_attribute(("modified", modified.as_str()));
+    }
+    start.push_attribute(("depth", entry.depth.to_string().as_str()));
+    if let Some(count) = entry.entry_count {
+        start.push_attribute(("entry_count", count.to_string().as_str()));
+    }
+    if let Some(bytes) = entry.subtree_bytes {
+        start.push_attribute(("subtree_bytes", bytes.to_string().as_str()));
+    }
+
+    if entry.children.is_empty() {
+        writer.write_event(XmlEvent::Empty(start))?;
+    } else {
+        writer.write_event(XmlEvent::Start(start))?;
+        for child in &entry.children {
+            write_xml_entry(writer, child)?;
+        }
+        writer.write_event(XmlEvent::End(BytesEnd::new("entry")))?;
+    }
+    Ok(())
 }
 
-#[derive(Debug, Serialize)]
-struct JsonSummary {
-    total_bytes: u64,
-    total_files: u64,
-    total_dirs: u64,
-    extensions: Option<BTreeMap<String, ExtSummary>>,
-}
+// Tree listings are emitted parent-first, so a stack of open ancestors is enough to nest them.
+// The root (".") sits one level above the depth-0 entries.
+fn build_nested_json(entries: &[DisplayEntry]) -> Vec<JsonEntry> {
+    fn close(stack: &mut Vec<(usize, JsonEntry)>, roots: &mut Vec<JsonEntry>) {
+        if let Some((_, done)) = stack.pop() {
+            match stack.last_mut() {
+                Some((_, parent)) => parent.children.push(done),
+                None => roots.push(done),
+            }
+        }
+    }
 
-impl DisplayEntry {
-    fn to_json(&self) -> JsonEntry {
-        let kind = if self.entry.is_dir() {
-            "dir"
-        } else if self.entry.is_symlink() {
-            "symlink"
-        } else {
-            "file"
-        };
-        let rel = normalize_match_path(&self.rel_path);
-        let name = self.entry.name.to_string_lossy().to_string();
-        let depth = if self.rel_path == Path::new(".") {
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, JsonEntry)> = Vec::new();
+    for entry in entries {
+        let level = if entry.rel_path == Path::new(".") {
             0
         } else {
-            self.rel_path.components().count().saturating_sub(1)
+            entry.rel_path.components().count()
         };
-        let modified = self
-            .entry
-            .modified
-            .map(humantime::format_rfc3339)
-            .map(|s| s.to_string());
-        JsonEntry {
-            rel_path: rel,
-            name,
-            kind: kind.to_string(),
-            size: self.entry.size(),
-            modified,
-            depth,
+        while stack.last().is_some_and(|(top, _)| *top >= level) {
+            close(&mut stack, &mut roots);
         }
+        stack.push((level, entry.to_json()));
     }
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+    roots
 }
 
 impl ListingSummary {
@@ -869,18 +4583,142 @@ impl ListingSummary {
 struct BrowserState {
     cwd: PathBuf,
     options: ListOptions,
+    matcher: Option<EntryFilter>,
+    git_branch: Option<GitBranchCache>,
+    preview_height: Option<usize>,
+    last_search: Option<EntryFilter>,
+    pending_cmd: Option<PathBuf>,
+    no_panel: bool,
+}
+
+type SummarySizeView = ResizedView<Panel<cursive::views::NamedView<TextView>>>;
+
+// `height` counts text lines; the panel border adds two more.
+fn preview_height_constraint(height: Option<usize>) -> SizeConstraint {
+    height.map_or(SizeConstraint::Full, |h| SizeConstraint::Fixed(h + 2))
+}
+
+// Starts from half the screen when no height was set, since a full-height panel has no fixed size.
+fn resize_preview(siv: &mut Cursive, delta: isize) {
+    let screen_height = siv.screen_size().y;
+    let height = siv.with_user_data(|state: &mut BrowserState| {
+        let current = state.preview_height.unwrap_or(screen_height / 2);
+        let height = current
+            .saturating_add_signed(delta)
+            .clamp(1, screen_height.max(1));
+        state.preview_height = Some(height);
+        height
+    });
+    if let Some(height) = height {
+        siv.call_on_name("summary_size", |view: &mut SummarySizeView| {
+            view.set_height(preview_height_constraint(Some(height)));
+        });
+    }
+}
+
+#[derive(Debug)]
+struct GitBranchCache {
+    dir: PathBuf,
+    fetched: Instant,
+    label: String,
+}
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+// A single click only moves the selection; the release of a second press on the same spot within
+// DOUBLE_CLICK_INTERVAL is let through so the SelectView submits the entry.
+fn double_click_filter<T>() -> impl Fn(&mut T, &Event) -> Option<EventResult> + Send + Sync {
+    let last_press = Mutex::new(None::<(Instant, cursive::Vec2)>);
+    let double = AtomicBool::new(false);
+    move |_, event| match event {
+        Event::Mouse {
+            event: MouseEvent::Press(MouseButton::Left),
+            position,
+            ..
+        } => {
+            let mut last = last_press.lock().unwrap_or_else(PoisonError::into_inner);
+            let is_double = last
+                .is_some_and(|(at, pos)| pos == *position && at.elapsed() <= DOUBLE_CLICK_INTERVAL);
+            double.store(is_double, AtomicOrdering::Relaxed);
+            *last = (!is_double).then(|| (Instant::now(), *position));
+            None
+        }
+        Event::Mouse {
+            event: MouseEvent::Release(MouseButton::Left),
+            ..
+        } if !double.swap(false, AtomicOrdering::Relaxed) => Some(EventResult::Consumed(None)),
+        _ => None,
+    }
+}
+
+const GIT_BRANCH_TTL: Duration = Duration::from_secs(5);
+
+fn cached_git_branch(state: &mut BrowserState) -> &str {
+    let fresh = state
+        .git_branch
+        .as_ref()
+        .is_some_and(|cache| cache.dir == state.cwd && cache.fetched.elapsed() < GIT_BRANCH_TTL);
+    if !fresh {
+        let label = ProcessCommand::new("git")
+            .arg("-C")
+            .arg(&state.cwd)
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_else(|| "no repo".to_string());
+        state.git_branch = Some(GitBranchCache {
+            dir: state.cwd.clone(),
+            fetched: Instant::now(),
+            label,
+        });
+    }
+    state.git_branch.as_ref().map_or("", |cache| &cache.label)
 }
 
 type EntriesScrollView = ScrollView<cursive::views::NamedView<SelectView<PathBuf>>>;
 
-fn run_interactive(start: PathBuf, options: ListOptions) -> Result<()> {
+const KEYBAR_TEXT: &str =
+    "Enter: open   Backspace: up   h: hidden   f or /: filter   r: refresh   q/Esc: quit";
+
+fn run_interactive(args: InteractiveArgs, mut options: ListOptions) -> Result<()> {
     let mut siv = cursive::crossterm();
     siv.set_theme(tui_theme());
+    options.fuzzy |= options.interactive_fuzzy;
 
-    let start = normalize_interactive_start(start)?;
+    let mut start = args.path.clone();
+    if args.restore_session {
+        let session = Session::load()?;
+        start = start.or(Some(session.cwd.clone()));
+        session.apply(&mut options);
+    }
+    if let Some(pattern) = args.initial_filter.clone() {
+        options.filter = Some(pattern);
+    }
+    let start = normalize_interactive_start(start.unwrap_or_else(|| PathBuf::from(".")))?;
+    let refresh_interval = args
+        .refresh_interval
+        .map(|secs| {
+            Duration::try_from_secs_f64(secs)
+                .ok()
+                .filter(|interval| !interval.is_zero())
+                .with_context(|| format!("Invalid refresh interval: {secs}"))
+        })
+        .transpose()?;
+    let matcher = compile_filter(&options)?;
+    // --interactive-grep counts as a search, so `n`/`N` work right away.
+    let last_search = matcher.clone().filter(|_| args.initial_filter.is_some());
     siv.set_user_data(BrowserState {
         cwd: start,
         options,
+        matcher,
+        git_branch: None,
+        preview_height: args.preview_height,
+        last_search,
+        pending_cmd: None,
+        no_panel: args.no_panel,
     });
 
     let list = SelectView::<PathBuf>::new()
@@ -897,35 +4735,67 @@ fn run_interactive(start: PathBuf, options: ListOptions) -> Result<()> {
                 set_summary_text(siv, &format!("{err:#}"));
             }
         })
-        .with_name("entries")
-        .full_height()
-        .scrollable()
-        .with_name("entries_scroll");
-
-    let summary = TextView::new("Select an entry")
-        .with_name("summary")
-        .full_height();
-
-    let content = LinearLayout::horizontal()
-        .child(Panel::new(list).title("Entries").full_height())
-        .child(ResizedView::with_min_width(
-            42,
-            Panel::new(summary).title("Summary"),
-        ));
+        .with_name("entries");
+    let mut list = OnEventView::new(list);
+    if args.mouse {
+        list.set_on_pre_event_inner(EventTrigger::mouse(), double_click_filter());
+    }
+    let list = list.full_height().scrollable().with_name("entries_scroll");
+
+    let summary = TextView::new("Select an entry").with_name("summary");
+    let summary = LinearLayout::vertical()
+        .child(
+            ResizedView::new(
+                SizeConstraint::Free,
+                preview_height_constraint(args.preview_height),
+                Panel::new(summary).title("Summary"),
+            )
+            .with_name("summary_size"),
+        )
+        .child(DummyView);
+
+    let content = match args.split {
+        _ if args.no_panel => LinearLayout::vertical()
+            .child(Panel::new(list).title("Entries").full_screen())
+            .child(ResizedView::with_fixed_height(
+                1,
+                TextView::new("").with_name("summary"),
+            )),
+        SplitDirection::Horizontal => LinearLayout::horizontal()
+            .child(Panel::new(list).title("Entries").full_screen())
+            .child(ResizedView::with_min_width(42, summary)),
+        SplitDirection::Vertical => LinearLayout::vertical()
+            .child(Panel::new(list).title("Entries").full_screen())
+            .child(summary),
+    };
 
-    let keybar = ResizedView::with_fixed_height(
-        1,
-        TextView::new("Enter: open   Backspace: up   h: hidden   r: refresh   q/Esc: quit"),
-    );
+    let keybar = ResizedView::with_fixed_height(1, TextView::new(KEYBAR_TEXT).with_name("keybar"));
 
-    let layout = LinearLayout::vertical().child(content).child(keybar);
+    let mut layout = LinearLayout::vertical().child(content).child(keybar);
+    if args.status_bar {
+        layout.add_child(ResizedView::with_fixed_height(
+            1,
+            TextView::new("").with_name("status_bar"),
+        ));
+    }
 
     let layout = Dialog::around(layout).title("lz");
     let root = LinearLayout::vertical()
         .child(layout)
-        .child(DummyView.fixed_height(2));
+        .child(DummyView.fixed_height(2))
+        .full_screen();
     siv.add_layer(root);
 
+    // Replaces cursive's default resize handler (a plain clear); once the layout has been
+    // recomputed for the new size, bring the selected entry back into view.
+    siv.set_on_pre_event(Event::WindowResize, |s| {
+        s.clear();
+        let _ = s.cb_sink().send(Box::new(|s| {
+            s.call_on_name("entries_scroll", |view: &mut EntriesScrollView| {
+                view.scroll_to_important_area();
+            });
+        }));
+    });
     siv.add_global_callback('q', |s| s.quit());
     siv.add_global_callback(Event::Key(Key::Esc), |s| s.quit());
     siv.add_global_callback(Event::Key(Key::Backspace), |s| {
@@ -943,12 +4813,153 @@ fn run_interactive(start: PathBuf, options: ListOptions) -> Result<()> {
             set_summary_text(s, &format!("{err:#}"));
         }
     });
+    siv.add_global_callback('f', interactive_open_filter);
+    siv.add_global_callback('/', interactive_open_filter);
+    if args.cmd.is_some() {
+        siv.add_global_callback('x', |s| {
+            let selected = s
+                .call_on_name("entries", |view: &mut SelectView<PathBuf>| view.selection())
+                .flatten();
+            if let Some(path) = selected {
+                s.with_user_data(|state: &mut BrowserState| {
+                    state.pending_cmd = Some(path.to_path_buf())
+                });
+                s.quit();
+            }
+        });
+    }
+    siv.add_global_callback('n', |s| interactive_find(s, true));
+    siv.add_global_callback('N', |s| interactive_find(s, false));
+    if siv
+        .user_data::<BrowserState>()
+        .is_some_and(|state| state.options.delta_integration)
+    {
+        siv.add_global_callback('v', |s| {
+            if let Err(err) = interactive_show_diff(s) {
+                set_summary_text(s, &format!("{err:#}"));
+            }
+        });
+    }
+    siv.add_global_callback('+', |s| resize_preview(s, 1));
+    siv.add_global_callback('-', |s| resize_preview(s, -1));
+    siv.add_global_callback('t', |s| {
+        if !tmux_enabled(s) {
+            return;
+        }
+        let selected = s
+            .call_on_name("entries", |view: &mut SelectView<PathBuf>| view.selection())
+            .flatten();
+        if let Some(path) = selected.filter(|path| path.is_file())
+            && let Err(err) = tmux_open(&path, TmuxTarget::Window)
+        {
+            set_summary_text(s, &format!("{err:#}"));
+        }
+    });
 
     interactive_reload(&mut siv)?;
+    let stop_refresh = Arc::new(AtomicBool::new(false));
+    if let Some(interval) = refresh_interval {
+        let sink = siv.cb_sink().clone();
+        let stop = Arc::clone(&stop_refresh);
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if stop.load(AtomicOrdering::Relaxed) {
+                    break;
+                }
+                let refresh = Box::new(|s: &mut Cursive| {
+                    if let Err(err) = interactive_refresh(s) {
+                        set_summary_text(s, &format!("{err:#}"));
+                    }
+                });
+                if sink.send(refresh).is_err() {
+                    break;
+                }
+            }
+        });
+    }
     siv.run();
+    while let Some(path) = siv
+        .with_user_data(|state: &mut BrowserState| state.pending_cmd.take())
+        .flatten()
+    {
+        if let Some(cmd) = &args.cmd
+            && let Err(err) = interactive_run_cmd(&mut siv, cmd, path)
+        {
+            set_summary_text(&mut siv, &format!("{err:#}"));
+        }
+        siv.run();
+    }
+    stop_refresh.store(true, AtomicOrdering::Relaxed);
+
+    // The TUI draws to /dev/tty, so stdout is free for the shell to capture.
+    if args.on_exit_cd
+        && let Some(state) = siv.user_data::<BrowserState>()
+    {
+        println!("cd {}", shell_quote(&state.cwd.to_string_lossy()));
+    }
+    if args.save_session
+        && let Some(state) = siv.user_data::<BrowserState>()
+    {
+        Session::from_state(state).save()?;
+    }
     Ok(())
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    cwd: PathBuf,
+    sort: SortKey,
+    reverse: bool,
+    all: bool,
+    filter: Option<String>,
+    fuzzy: bool,
+}
+
+impl Session {
+    fn path() -> Result<PathBuf> {
+        config_dir()
+            .map(|dir| dir.join("session.json"))
+            .context("Cannot locate the config directory for the session file")
+    }
+
+    fn from_state(state: &BrowserState) -> Self {
+        Session {
+            cwd: state.cwd.clone(),
+            sort: state.options.sort,
+            reverse: state.options.reverse,
+            all: state.options.all,
+            filter: state.options.filter.clone(),
+            fuzzy: state.options.fuzzy,
+        }
+    }
+
+    fn apply(self, options: &mut ListOptions) {
+        options.sort = self.sort;
+        options.reverse = self.reverse;
+        options.all = self.all;
+        options.filter = self.filter;
+        options.fuzzy = self.fuzzy;
+    }
+
+    fn load() -> Result<Self> {
+        let path = Self::path()?;
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read session {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Invalid session file {}", path.display()))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write session {}", path.display()))
+    }
+}
+
 fn normalize_interactive_start(start: PathBuf) -> Result<PathBuf> {
     let start = if start.is_absolute() {
         start
@@ -976,28 +4987,68 @@ fn tui_theme() -> Theme {
     theme
 }
 
+fn browser_entries(state: &BrowserState) -> Result<Vec<EntryInfo>> {
+    let options = &state.options;
+    let fuzzy = matches!(state.matcher, Some(EntryFilter::Fuzzy { .. }));
+    let mut entries = read_entries(&state.cwd, options)?;
+    entries.retain(|entry| {
+        (entry.is_dir() && !options.only_files && !fuzzy)
+            || should_print_entry(
+                entry,
+                Path::new(&entry.name),
+                options,
+                state.matcher.as_ref(),
+            )
+    });
+    sort_listing(&mut entries, options, None);
+    if fuzzy {
+        entries.sort_by_key(|e| std::cmp::Reverse(fuzzy_score(e, options)));
+    }
+    Ok(entries)
+}
+
 fn interactive_reload(siv: &mut Cursive) -> Result<()> {
-    let (cwd, options) = siv
+    let (cwd, options, entries) = siv
         .user_data::<BrowserState>()
-        .map(|s| (s.cwd.clone(), s.options.clone()))
+        .map(|s| (s.cwd.clone(), s.options.clone(), browser_entries(s)))
         .context("Missing browser state")?;
-
-    let mut entries = read_entries(&cwd, options.all)?;
-    sort_entries(&mut entries, options.sort, options.reverse);
+    let entries = entries?;
 
     let mut select = siv
         .find_name::<SelectView<PathBuf>>("entries")
         .context("Missing entries view")?;
     select.clear();
+    if options.include_parent
+        && let Some(parent) = cwd.parent()
+    {
+        select.add_item("../", parent.to_path_buf());
+    }
     for entry in &entries {
         let label = tui_label(entry, &options);
         select.add_item(label, entry.path.clone());
     }
+    let first = select.selection();
+    drop(select);
+
+    let branch = siv
+        .with_user_data(|state: &mut BrowserState| {
+            state
+                .options
+                .git_branch
+                .then(|| cached_git_branch(state).to_string())
+        })
+        .flatten();
+    match branch {
+        Some(branch) => {
+            siv.set_window_title(format!("lz interactive - {} [{branch}]", cwd.display()))
+        }
+        None => siv.set_window_title(format!("lz interactive - {}", cwd.display())),
+    }
+    set_keybar_text(siv, options.filter.as_deref());
+    set_status_bar_text(siv, entries.len(), &options);
 
-    siv.set_window_title(format!("lz interactive - {}", cwd.display()));
-
-    if let Some(first) = entries.first() {
-        update_summary(siv, &first.path)?;
+    if let Some(first) = first {
+        update_summary(siv, &first)?;
     } else {
         set_summary_text(siv, "(empty)");
     }
@@ -1005,22 +5056,69 @@ fn interactive_reload(siv: &mut Cursive) -> Result<()> {
     Ok(())
 }
 
-fn tui_label(entry: &EntryInfo, options: &ListOptions) -> String {
+// Like `interactive_reload`, but keeps the current selection if the entry still exists.
+fn interactive_refresh(siv: &mut Cursive) -> Result<()> {
+    let selected = siv
+        .call_on_name("entries", |view: &mut SelectView<PathBuf>| view.selection())
+        .flatten();
+    interactive_reload(siv)?;
+    let Some(selected) = selected else {
+        return Ok(());
+    };
+    let callback = siv
+        .call_on_name("entries", |view: &mut SelectView<PathBuf>| {
+            let index = view.iter().position(|(_, path)| *path == *selected)?;
+            Some(view.set_selection(index))
+        })
+        .flatten();
+    if let Some(callback) = callback {
+        callback(siv);
+    }
+    Ok(())
+}
+
+fn tui_label(entry: &EntryInfo, options: &ListOptions) -> StyledString {
     let icon = if options.icons {
-        if entry.is_dir() {
-            "📁 "
-        } else if entry.is_symlink() {
-            "🔗 "
+        let icon = if entry.is_dir() {
+            "📁"
+        } else if entry.is_symlink() && !options.no_symlink_icon {
+            "🔗"
         } else {
-            "📄 "
-        }
+            "📄"
+        };
+        format!("{icon}{:1$}", "", options.icons_padding)
     } else {
-        ""
+        String::new()
     };
 
-    let mut label = format!("{icon}{}", entry.name.to_string_lossy());
+    let color = options.colors.for_entry(entry).tui_color();
+    let name = entry.name.to_string_lossy();
+    let fuzzy = options
+        .filter
+        .as_deref()
+        .filter(|_| options.fuzzy)
+        .and_then(|pattern| skim_matcher(options.ignore_case).fuzzy_indices(&name, pattern));
+
+    let mut label = StyledString::styled(icon, color);
+    match &fuzzy {
+        Some((_, indices)) => {
+            let highlight = cursive::theme::Style::from(Color::Light(BaseColor::Yellow))
+                .combine(cursive::theme::Effect::Bold);
+            for (i, ch) in name.chars().enumerate() {
+                if indices.contains(&i) {
+                    label.append_styled(ch.to_string(), highlight);
+                } else {
+                    label.append_styled(ch.to_string(), color);
+                }
+            }
+        }
+        None => label.append_styled(name.as_ref(), color),
+    }
     if entry.is_dir() {
-        label.push(std::path::MAIN_SEPARATOR);
+        label.append_styled(std::path::MAIN_SEPARATOR.to_string(), color);
+    }
+    if let Some((score, _)) = fuzzy {
+        label.append_styled(format!("  ({score})"), Color::Light(BaseColor::White));
     }
     label
 }
@@ -1033,6 +5131,127 @@ fn interactive_toggle_hidden(siv: &mut Cursive) -> Result<()> {
     interactive_reload(siv)
 }
 
+fn interactive_open_filter(siv: &mut Cursive) {
+    let current = siv
+        .user_data::<BrowserState>()
+        .and_then(|s| s.options.filter.clone())
+        .unwrap_or_default();
+
+    let fuzzy = siv
+        .user_data::<BrowserState>()
+        .is_some_and(|s| s.options.fuzzy);
+
+    let mut input = EditView::new().content(current).on_submit(|s, text| {
+        s.pop_layer();
+        if let Err(err) = interactive_set_filter(s, text) {
+            set_summary_text(s, &format!("{err:#}"));
+        }
+    });
+    if fuzzy {
+        input.set_on_edit(|s, text, _| {
+            if let Err(err) = interactive_set_filter(s, text) {
+                set_summary_text(s, &format!("{err:#}"));
+            }
+        });
+    }
+    let title = if fuzzy {
+        "Search (fuzzy)"
+    } else {
+        "Filter (glob)"
+    };
+
+    let dialog = OnEventView::new(Dialog::around(input.min_width(32)).title(title)).on_event(
+        Key::Esc,
+        |s| {
+            s.pop_layer();
+            if let Err(err) = interactive_set_filter(s, "") {
+                set_summary_text(s, &format!("{err:#}"));
+            }
+        },
+    );
+    siv.add_layer(dialog);
+}
+
+fn interactive_set_filter(siv: &mut Cursive, pattern: &str) -> Result<()> {
+    let mut options = siv
+        .user_data::<BrowserState>()
+        .map(|s| s.options.clone())
+        .context("Missing browser state")?;
+    options.filter = if pattern.trim().is_empty() {
+        None
+    } else {
+        Some(pattern.to_string())
+    };
+    let matcher = compile_filter(&options)?;
+
+    siv.with_user_data(|state: &mut BrowserState| {
+        state.options = options;
+        if matcher.is_some() {
+            state.last_search = matcher.clone();
+        }
+        state.matcher = matcher;
+    })
+    .context("Missing browser state")?;
+    interactive_reload(siv)
+}
+
+// Moves the selection to the next (or previous) entry matching the last `/` search, wrapping
+// around. Directories kept visible by a glob filter are skipped unless they match themselves.
+fn interactive_find(siv: &mut Cursive, forward: bool) {
+    let Some(search) = siv
+        .user_data::<BrowserState>()
+        .and_then(|state| state.last_search.clone())
+    else {
+        return;
+    };
+    let callback = siv.call_on_name("entries", |view: &mut SelectView<PathBuf>| {
+        let len = view.len();
+        let start = view.selected_id()?;
+        let found = (1..=len)
+            .map(|step| {
+                if forward {
+                    (start + step) % len
+                } else {
+                    (start + len - step) % len
+                }
+            })
+            .find(|&index| {
+                view.get_item(index).is_some_and(|(_, path)| {
+                    let name = path.file_name().unwrap_or(path.as_os_str());
+                    search.is_match(Path::new(name), name)
+                })
+            })?;
+        Some(view.set_selection(found))
+    });
+    if let Some(callback) = callback.flatten() {
+        callback(siv);
+    }
+}
+
+fn set_keybar_text(siv: &mut Cursive, filter: Option<&str>) {
+    if let Some(mut view) = siv.find_name::<TextView>("keybar") {
+        match filter {
+            Some(pattern) => view.set_content(format!("{KEYBAR_TEXT}   [filter: {pattern}]")),
+            None => view.set_content(KEYBAR_TEXT),
+        }
+    }
+}
+
+fn set_status_bar_text(siv: &mut Cursive, entries: usize, options: &ListOptions) {
+    if let Some(mut view) = siv.find_name::<TextView>("status_bar") {
+        let sort = options
+            .sort
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string());
+        view.set_content(format!(
+            "{entries} entries | sort: {sort}{} | filter: {} | hidden: {}",
+            if options.reverse { " (reversed)" } else { "" },
+            options.filter.as_deref().unwrap_or("none"),
+            if options.all { "on" } else { "off" },
+        ));
+    }
+}
+
 fn interactive_go_up(siv: &mut Cursive) -> Result<()> {
     siv.with_user_data(|state: &mut BrowserState| {
         if let Some(parent) = state.cwd.parent() {
@@ -1043,16 +5262,286 @@ fn interactive_go_up(siv: &mut Cursive) -> Result<()> {
     interactive_reload(siv)
 }
 
-fn interactive_open_or_select(siv: &mut Cursive, path: &Path) -> Result<()> {
-    let md = fs::symlink_metadata(path)?;
-    if md.is_dir() {
-        siv.with_user_data(|state: &mut BrowserState| state.cwd = path.to_path_buf())
-            .context("Missing browser state")?;
-        interactive_reload(siv)?;
-    } else {
-        update_summary(siv, path)?;
+fn interactive_open_or_select(siv: &mut Cursive, path: &Path) -> Result<()> {
+    let md = fs::symlink_metadata(path)?;
+    if md.is_dir() {
+        siv.with_user_data(|state: &mut BrowserState| state.cwd = path.to_path_buf())
+            .context("Missing browser state")?;
+        interactive_reload(siv)?;
+    } else {
+        update_summary(siv, path)?;
+        if md.is_file() && tmux_enabled(siv) {
+            return tmux_open(path, TmuxTarget::Split);
+        }
+        let preview_cmd =
+            siv.user_data::<BrowserState>()
+                .and_then(|state| match &state.options.preview_cmd {
+                    Some(cmd) => Some(cmd.clone()),
+                    None if state.options.bat_integration => Some(DEFAULT_PREVIEW_CMD.to_string()),
+                    None => None,
+                });
+        if let Some(cmd) = preview_cmd.filter(|_| md.is_file()) {
+            show_file_preview(siv, path, &cmd);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum TmuxTarget {
+    Split,
+    Window,
+}
+
+fn tmux_enabled(siv: &mut Cursive) -> bool {
+    siv.user_data::<BrowserState>()
+        .is_some_and(|state| state.options.tmux_integration)
+        && env::var_os("TMUX").is_some()
+}
+
+fn tmux_open(path: &Path, target: TmuxTarget) -> Result<()> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut cmd = ProcessCommand::new("tmux");
+    match target {
+        TmuxTarget::Split => cmd.args(["split-window", "-h"]),
+        TmuxTarget::Window => cmd.arg("new-window"),
+    };
+    // With more than one argument tmux execs the command directly, so no shell quoting is needed.
+    let status = cmd
+        .args(editor.split_whitespace())
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("Failed to run tmux")?;
+    if !status.success() {
+        anyhow::bail!("tmux exited with {status}");
+    }
+    Ok(())
+}
+
+// The command gets the real terminal: the TUI loop is stopped (which restores the terminal), the
+// command runs, and the loop is started again with a fresh backend.
+fn interactive_run_cmd(siv: &mut Cursive, cmd: &str, path: PathBuf) -> Result<()> {
+    let mut parts = cmd.split_whitespace();
+    let program = parts.next().context("Empty command")?;
+    let status = ProcessCommand::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run {cmd}"))?;
+    update_summary(siv, &path)?;
+    if let Some(mut view) = siv.find_name::<TextView>("summary") {
+        view.append(format!("\n$ {cmd} {}\n{status}", path.display()));
+    }
+    Ok(())
+}
+
+const DEFAULT_PREVIEW_CMD: &str = "bat --style=plain --color=always";
+
+fn show_file_preview(siv: &mut Cursive, path: &Path, cmd: &str) {
+    let content = match run_preview_cmd(cmd, path) {
+        Some(output) => parse_ansi(&output),
+        None => StyledString::plain(read_preview_fallback(path)),
+    };
+    let title = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    show_text_layer(siv, title, content);
+}
+
+fn show_text_layer(siv: &mut Cursive, title: String, content: StyledString) {
+    let view = Dialog::around(TextView::new(content).scrollable())
+        .title(title)
+        .button("Close", |s| {
+            s.pop_layer();
+        })
+        .full_screen();
+    let view = OnEventView::new(view)
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        })
+        .on_event('q', |s| {
+            s.pop_layer();
+        });
+    siv.add_layer(view);
+}
+
+fn interactive_show_diff(siv: &mut Cursive) -> Result<()> {
+    let Some(path) = siv
+        .call_on_name("entries", |view: &mut SelectView<PathBuf>| view.selection())
+        .flatten()
+    else {
+        return Ok(());
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let name = path.file_name().unwrap_or(path.as_os_str());
+
+    let status = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain", "--"])
+        .arg(name)
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run git")?;
+    if !status.status.success() {
+        anyhow::bail!("{} is not inside a git repository", path.display());
+    }
+    if !status.stdout.get(..2).is_some_and(|xy| xy.contains(&b'M')) {
+        anyhow::bail!("{} has no modifications", path.display());
+    }
+
+    let diff = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["diff", "--color=always", "HEAD", "--"])
+        .arg(name)
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run git diff")?;
+    let highlighted = ["delta --paging=never", "diff-highlight"]
+        .iter()
+        .find_map(|cmd| pipe_through(cmd, &diff.stdout));
+    let text = highlighted.unwrap_or_else(|| String::from_utf8_lossy(&diff.stdout).into_owned());
+    show_text_layer(
+        siv,
+        format!("git diff {}", name.to_string_lossy()),
+        parse_ansi(&text),
+    );
+    Ok(())
+}
+
+// `None` when the command is missing or fails, so the caller can fall back to the raw input.
+fn pipe_through(cmd: &str, input: &[u8]) -> Option<String> {
+    let mut parts = cmd.split_whitespace();
+    let mut child = ProcessCommand::new(parts.next()?)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Write from a separate thread so a large diff cannot deadlock against a full stdout pipe.
+    let mut stdin = child.stdin.take()?;
+    let input = input.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().ok()?;
+    let _ = writer.join();
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Handles the SGR subset that `bat` and similar highlighters emit; other escapes are dropped.
+fn parse_ansi(text: &str) -> StyledString {
+    use cursive::theme::{Effect, Style as TuiStyle};
+
+    let mut styled = StyledString::new();
+    let mut fg: Option<Color> = None;
+    let mut effects: Vec<Effect> = Vec::new();
+    let mut rest = text;
+    let current = |fg: Option<Color>, effects: &[Effect]| {
+        effects.iter().fold(
+            fg.map(TuiStyle::from).unwrap_or_default(),
+            |style, effect| style.combine(*effect),
+        )
+    };
+
+    while let Some(start) = rest.find('\x1b') {
+        let plain = &rest[..start];
+        if !plain.is_empty() {
+            styled.append_styled(plain, current(fg, &effects));
+        }
+        rest = &rest[start + 1..];
+        let Some(seq) = rest.strip_prefix('[') else {
+            continue;
+        };
+        let Some(end) = seq.find(|c: char| c.is_ascii_alphabetic()) else {
+            rest = "";
+            break;
+        };
+        rest = &seq[end + 1..];
+        if !seq[end..].starts_with('m') {
+            continue;
+        }
+
+        let codes: Vec<u8> = seq[..end]
+            .split(';')
+            .map(|code| code.parse().unwrap_or(0))
+            .collect();
+        let mut codes = codes.into_iter();
+        while let Some(code) = codes.next() {
+            match code {
+                0 => {
+                    fg = None;
+                    effects.clear();
+                }
+                1 => effects.push(Effect::Bold),
+                3 => effects.push(Effect::Italic),
+                4 => effects.push(Effect::Underline),
+                30..=37 => fg = Some(Color::Dark(ansi_base_color(code - 30))),
+                90..=97 => fg = Some(Color::Light(ansi_base_color(code - 90))),
+                39 => fg = None,
+                38 => match codes.next() {
+                    Some(5) => fg = codes.next().map(Color::from_256colors),
+                    Some(2) => {
+                        if let (Some(r), Some(g), Some(b)) =
+                            (codes.next(), codes.next(), codes.next())
+                        {
+                            fg = Some(Color::Rgb(r, g, b));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+    if !rest.is_empty() {
+        styled.append_styled(rest, current(fg, &effects));
+    }
+    styled
+}
+
+fn ansi_base_color(index: u8) -> BaseColor {
+    match index {
+        0 => BaseColor::Black,
+        1 => BaseColor::Red,
+        2 => BaseColor::Green,
+        3 => BaseColor::Yellow,
+        4 => BaseColor::Blue,
+        5 => BaseColor::Magenta,
+        6 => BaseColor::Cyan,
+        _ => BaseColor::White,
+    }
+}
+
+// `None` when the command is missing or fails, so the caller can fall back to plain text.
+fn run_preview_cmd(cmd: &str, path: &Path) -> Option<String> {
+    let mut parts = cmd.split_whitespace();
+    let output = ProcessCommand::new(parts.next()?)
+        .args(parts)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_preview_fallback(path: &Path) -> String {
+    let mut head = Vec::new();
+    match fs::File::open(path).and_then(|f| f.take(256 * 1024).read_to_end(&mut head)) {
+        Ok(_) if head.contains(&0) => "(binary file)".to_string(),
+        Ok(_) => String::from_utf8_lossy(&head).into_owned(),
+        Err(err) => format!("Failed to read {}: {err}", path.display()),
     }
-    Ok(())
 }
 
 fn update_summary(siv: &mut Cursive, path: &Path) -> Result<()> {
@@ -1067,23 +5556,44 @@ fn update_summary(siv: &mut Cursive, path: &Path) -> Result<()> {
         "File"
     };
 
-    let mut text = String::new();
-    text.push_str(&format!("Path: {}\n", path.display()));
-    text.push_str(&format!("Type: {kind}\n"));
-    text.push_str(&format!(
-        "Modified: {}\n",
-        md.modified()
-            .ok()
-            .map(humantime::format_rfc3339)
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "-".to_string())
-    ));
+    let (options, no_panel) = siv
+        .with_user_data(|state: &mut BrowserState| (state.options.clone(), state.no_panel))
+        .context("Missing browser state")?;
 
-    if file_type.is_file() {
-        text.push_str(&format!("Size: {}\n", format_size(md.len(), true)));
+    let modified = md
+        .modified()
+        .ok()
+        .map(|t| format_timestamp(t, &options, true))
+        .unwrap_or_else(|| "-".to_string());
+    let detail = if file_type.is_file() {
+        Some(("Size", format_size(md.len(), true)))
     } else if file_type.is_dir() {
         let (dirs, files) = count_children(path)?;
-        text.push_str(&format!("Children: {dirs} dirs, {files} files\n"));
+        Some(("Children", format!("{dirs} dirs, {files} files")))
+    } else {
+        None
+    };
+
+    if no_panel {
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        let mut line = format!("{name} | {kind}");
+        if let Some((_, value)) = &detail {
+            line.push_str(&format!(" | {value}"));
+        }
+        line.push_str(&format!(" | {modified}"));
+        set_summary_text(siv, &line);
+        return Ok(());
+    }
+
+    let mut text = String::new();
+    text.push_str(&format!("Path: {}\n", path.display()));
+    text.push_str(&format!("Type: {kind}\n"));
+    text.push_str(&format!("Modified: {modified}\n"));
+    if let Some((label, value)) = detail {
+        text.push_str(&format!("{label}: {value}\n"));
     }
 
     text.push_str(&format!(
@@ -1095,10 +5605,50 @@ fn update_summary(siv: &mut Cursive, path: &Path) -> Result<()> {
         }
     ));
 
+    if let Some(n) = options.git_log.filter(|_| file_type.is_file()) {
+        text.push_str("\nGit log:\n");
+        match git_log_lines(path, n) {
+            Some(lines) if !lines.is_empty() => {
+                for line in lines {
+                    text.push_str(&format!("{line}\n"));
+                }
+            }
+            _ => text.push_str("(no git history)\n"),
+        }
+    }
+
     set_summary_text(siv, &text);
     Ok(())
 }
 
+fn git_log_lines(path: &Path, n: usize) -> Option<Vec<String>> {
+    const MESSAGE_W: usize = 40;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let output = ProcessCommand::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("log")
+        .arg(format!("-{n}"))
+        .args(["--oneline", "--"])
+        .arg(path.file_name()?)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let lines = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| match line.split_once(' ') {
+            Some((hash, message)) if message.chars().count() > MESSAGE_W => {
+                let message: String = message.chars().take(MESSAGE_W - 1).collect();
+                format!("{hash} {message}…")
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+    Some(lines)
+}
+
 fn count_children(dir: &Path) -> Result<(u64, u64)> {
     let mut dirs = 0u64;
     let mut files = 0u64;
@@ -1120,6 +5670,235 @@ fn set_summary_text(siv: &mut Cursive, text: &str) {
     }
 }
 
+#[derive(Debug)]
+struct TwoPanelState {
+    panels: [BrowserState; 2],
+    active: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transfer {
+    Copy,
+    Move,
+}
+
+const PANEL_NAMES: [&str; 2] = ["left_entries", "right_entries"];
+const PANEL_HEADERS: [&str; 2] = ["left_header", "right_header"];
+
+const TWO_PANEL_KEYBAR_TEXT: &str =
+    "Tab: switch   Enter: open   Backspace: up   F5: copy   F6: move   r: refresh   q/Esc: quit";
+
+fn run_two_panel(left: PathBuf, right: PathBuf, options: ListOptions) -> Result<()> {
+    let mut siv = cursive::crossterm();
+    siv.set_theme(tui_theme());
+
+    let matcher = compile_filter(&options)?;
+    let panel = |start: PathBuf| -> Result<BrowserState> {
+        Ok(BrowserState {
+            cwd: normalize_interactive_start(start)?,
+            options: options.clone(),
+            matcher: matcher.clone(),
+            git_branch: None,
+            preview_height: None,
+            last_search: None,
+            pending_cmd: None,
+            no_panel: false,
+        })
+    };
+    siv.set_user_data(TwoPanelState {
+        panels: [panel(left)?, panel(right)?],
+        active: 0,
+    });
+
+    let mut columns = LinearLayout::horizontal();
+    for side in 0..2 {
+        let list = SelectView::<PathBuf>::new()
+            .on_submit(move |siv, path| {
+                if let Err(err) = two_panel_open(siv, side, path) {
+                    set_two_panel_status(siv, &format!("{err:#}"));
+                }
+            })
+            .with_name(PANEL_NAMES[side])
+            .scrollable()
+            .full_screen();
+        let column = LinearLayout::vertical()
+            .child(TextView::new("").with_name(PANEL_HEADERS[side]))
+            .child(list);
+        columns.add_child(Panel::new(column).full_screen());
+    }
+
+    // Focus changes go through `two_panel_focus` so the active panel and the focused view agree.
+    let columns = OnEventView::new(columns)
+        .on_pre_event(Event::Key(Key::Tab), |s| {
+            let other = s
+                .user_data::<TwoPanelState>()
+                .map_or(0, |state| 1 - state.active);
+            two_panel_focus(s, other);
+        })
+        .on_pre_event(Event::Key(Key::Left), |s| two_panel_focus(s, 0))
+        .on_pre_event(Event::Key(Key::Right), |s| two_panel_focus(s, 1));
+
+    let layout = LinearLayout::vertical()
+        .child(columns)
+        .child(TextView::new("").with_name("two_panel_status"))
+        .child(TextView::new(TWO_PANEL_KEYBAR_TEXT));
+    siv.add_fullscreen_layer(Dialog::around(layout).title("lz").full_screen());
+
+    siv.add_global_callback('q', |s| s.quit());
+    siv.add_global_callback(Event::Key(Key::Esc), |s| s.quit());
+    siv.add_global_callback(Event::Key(Key::Backspace), |s| {
+        if let Err(err) = two_panel_go_up(s) {
+            set_two_panel_status(s, &format!("{err:#}"));
+        }
+    });
+    siv.add_global_callback('r', |s| {
+        if let Err(err) = two_panel_reload_all(s) {
+            set_two_panel_status(s, &format!("{err:#}"));
+        }
+    });
+    siv.add_global_callback(Event::Key(Key::F5), |s| {
+        two_panel_transfer_and_report(s, Transfer::Copy)
+    });
+    siv.add_global_callback(Event::Key(Key::F6), |s| {
+        two_panel_transfer_and_report(s, Transfer::Move)
+    });
+
+    two_panel_reload_all(&mut siv)?;
+    two_panel_focus(&mut siv, 0);
+    siv.run();
+    Ok(())
+}
+
+fn two_panel_focus(siv: &mut Cursive, side: usize) {
+    if let Some(state) = siv.user_data::<TwoPanelState>() {
+        state.active = side;
+    }
+    let _ = siv.focus_name(PANEL_NAMES[side]);
+    two_panel_update_headers(siv);
+}
+
+fn two_panel_update_headers(siv: &mut Cursive) {
+    let Some((active, cwds)) = siv.user_data::<TwoPanelState>().map(|state| {
+        (
+            state.active,
+            [state.panels[0].cwd.clone(), state.panels[1].cwd.clone()],
+        )
+    }) else {
+        return;
+    };
+    for (side, cwd) in cwds.iter().enumerate() {
+        let marker = if side == active { "> " } else { "  " };
+        siv.call_on_name(PANEL_HEADERS[side], |view: &mut TextView| {
+            view.set_content(format!("{marker}{}", cwd.display()));
+        });
+    }
+}
+
+fn two_panel_reload(siv: &mut Cursive, side: usize) -> Result<()> {
+    let (options, entries) = siv
+        .user_data::<TwoPanelState>()
+        .map(|state| {
+            let panel = &state.panels[side];
+            (panel.options.clone(), browser_entries(panel))
+        })
+        .context("Missing two-panel state")?;
+    let entries = entries?;
+
+    let mut select = siv
+        .find_name::<SelectView<PathBuf>>(PANEL_NAMES[side])
+        .context("Missing panel view")?;
+    select.clear();
+    for entry in &entries {
+        select.add_item(tui_label(entry, &options), entry.path.clone());
+    }
+    drop(select);
+
+    two_panel_update_headers(siv);
+    Ok(())
+}
+
+fn two_panel_reload_all(siv: &mut Cursive) -> Result<()> {
+    two_panel_reload(siv, 0)?;
+    two_panel_reload(siv, 1)
+}
+
+fn two_panel_open(siv: &mut Cursive, side: usize, path: &Path) -> Result<()> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Ok(());
+    }
+    siv.with_user_data(|state: &mut TwoPanelState| {
+        state.panels[side].cwd = path.to_path_buf();
+    })
+    .context("Missing two-panel state")?;
+    two_panel_reload(siv, side)
+}
+
+fn two_panel_go_up(siv: &mut Cursive) -> Result<()> {
+    let side = siv
+        .with_user_data(|state: &mut TwoPanelState| {
+            let panel = &mut state.panels[state.active];
+            if let Some(parent) = panel.cwd.parent() {
+                panel.cwd = parent.to_path_buf();
+            }
+            state.active
+        })
+        .context("Missing two-panel state")?;
+    two_panel_reload(siv, side)
+}
+
+fn two_panel_transfer_and_report(siv: &mut Cursive, op: Transfer) {
+    let message = match two_panel_transfer(siv, op) {
+        Ok(message) => message,
+        Err(err) => format!("{err:#}"),
+    };
+    set_two_panel_status(siv, &message);
+}
+
+fn two_panel_transfer(siv: &mut Cursive, op: Transfer) -> Result<String> {
+    let (active, dest_dir) = siv
+        .user_data::<TwoPanelState>()
+        .map(|state| (state.active, state.panels[1 - state.active].cwd.clone()))
+        .context("Missing two-panel state")?;
+    let src = siv
+        .find_name::<SelectView<PathBuf>>(PANEL_NAMES[active])
+        .and_then(|select| select.selection())
+        .context("Nothing selected")?;
+    let name = src.file_name().context("Cannot transfer this entry")?;
+    let dest = dest_dir.join(name);
+    if dest.exists() {
+        anyhow::bail!("{} already exists", dest.display());
+    }
+
+    let verb = match op {
+        Transfer::Copy => {
+            if fs::symlink_metadata(src.as_path())?.is_dir() {
+                anyhow::bail!("Copying directories is not supported");
+            }
+            fs::copy(src.as_path(), &dest)
+                .with_context(|| format!("Failed to copy {}", src.display()))?;
+            "Copied"
+        }
+        Transfer::Move => {
+            fs::rename(src.as_path(), &dest)
+                .with_context(|| format!("Failed to move {}", src.display()))?;
+            "Moved"
+        }
+    };
+
+    two_panel_reload_all(siv)?;
+    Ok(format!(
+        "{verb} {} to {}",
+        src.display(),
+        dest_dir.display()
+    ))
+}
+
+fn set_two_panel_status(siv: &mut Cursive, text: &str) {
+    if let Some(mut view) = siv.find_name::<TextView>("two_panel_status") {
+        view.set_content(text.to_string());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1136,6 +5915,172 @@ mod tests {
         }
     }
 
+    #[test]
+    fn remote_entries_are_filtered_sorted_and_serialized() {
+        let remote = |name: &str, kind, size| RemoteEntry {
+            name: name.to_string(),
+            kind,
+            size,
+            modified: None,
+            readonly: false,
+        };
+        let entries = || {
+            vec![
+                remote("small.txt", "file", 1),
+                remote(".hidden.txt", "file", 50),
+                remote("big.txt", "file", 100),
+                remote("notes.md", "file", 10),
+                remote("docs", "dir", 0),
+            ]
+        };
+        let names = |entries: &[RemoteEntry]| {
+            entries
+                .iter()
+                .map(|entry| entry.name.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let cli = Cli::parse_from(["lz", "--filter", "*.txt", "--sort", "size"]);
+        let listed = filter_remote_entries(entries(), &cli.options).unwrap();
+        assert_eq!(names(&listed), "big.txt,small.txt");
+
+        let cli = Cli::parse_from(["lz", "--only-dirs"]);
+        let listed = filter_remote_entries(entries(), &cli.options).unwrap();
+        assert_eq!(names(&listed), "docs");
+
+        let cli = Cli::parse_from(["lz", "--all", "--no-sort", "--json"]);
+        let listed = filter_remote_entries(entries(), &cli.options).unwrap();
+        assert_eq!(listed.len(), 5);
+        let json = serde_json::to_value(listed[2].to_json()).unwrap();
+        assert_eq!(json["name"], "big.txt");
+        assert_eq!(json["kind"], "file");
+        assert_eq!(json["size"], 100);
+
+        let cli = Cli::parse_from(["lz", "--tree"]);
+        assert!(reject_remote_options(&cli.options, "--s3").is_err());
+    }
+
+    #[test]
+    fn log_rotates_while_running() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("lz.log");
+        open_log(&path, 0).unwrap();
+        for entries in 1..=3 {
+            log_operation("list", td.path(), Some(entries));
+        }
+        LOG.lock().unwrap_or_else(PoisonError::into_inner).take();
+
+        let rotated = fs::read_to_string(td.path().join("lz.log.1")).unwrap();
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(rotated.lines().count(), 1);
+        assert!(rotated.contains("\"entries\":2"));
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("\"entries\":3"));
+    }
+
+    #[test]
+    fn effective_command_parses_back() {
+        let matches = Cli::command().get_matches_from([
+            "lz",
+            "--sort",
+            "size",
+            "-r",
+            "--search",
+            "ma",
+            "--no-progress",
+            "--print-cmd",
+        ]);
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        normalize_options(&mut cli.options);
+
+        let (args, defaults) = effective_args(&matches, &cli.options);
+        assert!(defaults.iter().any(|arg| arg == "--icons-padding"));
+        let parsed =
+            Cli::try_parse_from(["lz"].into_iter().chain(args.iter().map(String::as_str))).unwrap();
+        assert_eq!(parsed.options.sort, SortKey::Size);
+        assert!(parsed.options.reverse);
+        assert_eq!(parsed.options.filter.as_deref(), Some("*ma*"));
+        assert_eq!(parsed.options.search, None);
+    }
+
+    #[test]
+    fn reverse_tree_mirrors_prefixes() {
+        let td = tempfile::tempdir().unwrap();
+        fs::create_dir_all(td.path().join("sub/deep")).unwrap();
+        for file in ["script", "sub/x.rs", "sub/deep/d.txt"] {
+            fs::write(td.path().join(file), b"x").unwrap();
+        }
+
+        let cli = Cli::parse_from(["lz", "--tree", "--reverse-tree"]);
+        let entries =
+            build_display_entries_for_dir(td.path(), td.path(), &cli.options, None).unwrap();
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|e| format!("{}{}", e.prefix, e.entry.name.to_string_lossy()))
+            .collect();
+        let root = td.path().file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            lines,
+            vec![
+                "┌── script".to_string(),
+                "│   ┌── x.rs".to_string(),
+                "│   │   ┌── d.txt".to_string(),
+                "│   ├── deep".to_string(),
+                "├── sub".to_string(),
+                root.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn glob_to_regex_is_unanchored() {
+        assert_eq!(glob_to_regex("*.rs"), "\\.rs");
+        assert_eq!(glob_to_regex("a?c*d"), "a.c.*d");
+        let re = MetaRegex::new(&glob_to_regex("*ain*")).unwrap();
+        assert_eq!(re.find("main.rs").map(|m| m.range()), Some(1..4));
+    }
+
+    #[test]
+    fn nearest_256_maps_cube_and_grays() {
+        assert_eq!(nearest_256(255, 0, 0), 196);
+        assert_eq!(nearest_256(0, 0, 0), 16);
+        assert_eq!(nearest_256(255, 255, 255), 231);
+        assert_eq!(nearest_256(128, 128, 128), 244);
+    }
+
+    #[test]
+    fn parse_byte_size_units() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2 KiB"), Ok(2048));
+        assert!(parse_byte_size("5Q").is_err());
+    }
+
+    #[test]
+    fn compare_versions_numerically() {
+        assert_eq!(compare_versions("0.10.0", "0.9.3"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2.0-rc.1", "1.2.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn strip_ansi_removes_sequences() {
+        let text = "\x1b[1;31mred\x1b[0m \x1b]8;;https://x\x1b\\link\x1b]8;;\x07 plain";
+        assert_eq!(strip_ansi(text), "red link plain");
+    }
+
+    #[test]
+    fn parse_ansi_strips_escapes() {
+        let styled = parse_ansi("\x1b[1;31mfn\x1b[0m main\x1b[38;5;208m()\x1b[K");
+        assert_eq!(styled.source(), "fn main()");
+    }
+
     #[test]
     fn sort_dirs_first() {
         let td = tempfile::tempdir().unwrap();
@@ -1145,16 +6090,343 @@ mod tests {
         fs::write(&file, b"x").unwrap();
 
         let mut entries = vec![make_entry(&file), make_entry(&dir)];
-        sort_entries(&mut entries, SortKey::Name, false);
+        sort_entries(&mut entries, SortKey::Name, false, false, None);
 
         assert!(entries[0].is_dir());
         assert!(!entries[1].is_dir());
     }
 
+    #[test]
+    fn json_output_snapshot_deserializes() {
+        let snapshot = r#"{
+            "version": 1,
+            "root": ".",
+            "entries": [
+                {
+                    "rel_path": "src/main.rs",
+                    "name": "main.rs",
+                    "kind": "file",
+                    "size": 42,
+                    "modified": "2024-03-15T12:00:00.000000000Z",
+                    "depth": 1
+                }
+            ],
+            "summary": {
+                "total_bytes": 42,
+                "total_files": 1,
+                "total_dirs": 0,
+                "extensions": { "rs": { "files": 1, "bytes": 42 } }
+            },
+            "error": null
+        }"#;
+
+        let out: JsonOutput = serde_json::from_str(snapshot).unwrap();
+        assert_eq!(out.version, JSON_FORMAT_VERSION);
+        assert_eq!(out.entries.len(), 1);
+        assert_eq!(out.entries[0].rel_path, "src/main.rs");
+        assert_eq!(out.summary.unwrap().total_bytes, 42);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tree_does_not_recurse_into_symlinked_dirs() {
+        let td = tempfile::tempdir().unwrap();
+        let target = td.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("inner.txt"), b"x").unwrap();
+        let root = td.path().join("root");
+        fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink(&target, root.join("link")).unwrap();
+
+        let cli = Cli::parse_from(["lz", "--tree"]);
+        let entries = build_display_entries_for_dir(&root, &root, &cli.options, None).unwrap();
+        let rels: Vec<_> = entries
+            .iter()
+            .map(|e| normalize_match_path(&e.rel_path))
+            .collect();
+
+        assert_eq!(rels, vec![".", "link"]);
+    }
+
+    #[test]
+    fn show_count_skips_hidden_entries_without_all() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(td.path().join("visible"), b"x").unwrap();
+        fs::write(td.path().join(".dotfile"), b"x").unwrap();
+        let dir = make_entry(td.path());
+
+        let cli = Cli::parse_from(["lz", "--tree", "--show-count"]);
+        assert_eq!(tree_entry_count(&dir, &cli.options).unwrap(), Some(1));
+        let cli = Cli::parse_from(["lz", "--tree", "--show-count", "--all"]);
+        assert_eq!(tree_entry_count(&dir, &cli.options).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn filter_ignore_case() {
+        let td = tempfile::tempdir().unwrap();
+        for name in ["Main.RS", "lib.rs", "README.md"] {
+            fs::write(td.path().join(name), b"x").unwrap();
+        }
+
+        let matched = |args: &[&str]| {
+            let cli = Cli::parse_from(args);
+            let matcher = compile_filter(&cli.options).unwrap();
+            let entries =
+                build_display_entries_for_dir(td.path(), td.path(), &cli.options, matcher.as_ref())
+                    .unwrap();
+            entries
+                .iter()
+                .map(|e| e.entry.name.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched(&["lz", "--filter", "*.rs"]), vec!["lib.rs"]);
+        assert_eq!(
+            matched(&["lz", "--filter", "*.rs", "--ignore-case"]),
+            vec!["lib.rs", "Main.RS"]
+        );
+        assert_eq!(
+            matched(&["lz", "--filter", "readme*", "-i"]),
+            vec!["README.md"]
+        );
+    }
+
+    #[test]
+    fn color_config_from_toml() {
+        let config = ColorConfig::from_toml(
+            r##"
+            dir = "#FF5733"
+            symlink = "magenta"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.dir, ColorSpec::Rgb(0xFF, 0x57, 0x33));
+        assert_eq!(config.symlink, ColorSpec::Named(BaseColor::Magenta, false));
+        assert_eq!(config.file, ColorConfig::default().file);
+
+        assert!(ColorConfig::from_toml(r##"dir = "#12345""##).is_err());
+        assert!(ColorConfig::from_toml(r#"dir = "purple""#).is_err());
+        assert!(ColorConfig::from_toml(r#"folder = "red""#).is_err());
+    }
+
+    #[test]
+    fn integer_separators() {
+        assert_eq!(format_integer_with_separators(0, ','), "0");
+        assert_eq!(format_integer_with_separators(999, ','), "999");
+        assert_eq!(format_integer_with_separators(1000, ','), "1,000");
+        assert_eq!(
+            format_integer_with_separators(1234567890, '.'),
+            "1.234.567.890"
+        );
+    }
+
     #[test]
     fn format_size_human() {
         assert_eq!(format_size(0, true), "0 B");
         assert_eq!(format_size(1024, true), "1.0 KiB");
         assert_eq!(format_size(1536, true), "1.5 KiB");
     }
+
+    #[test]
+    fn listing_summary_roundtrip() {
+        let mut summary = ListingSummary {
+            total_bytes: 42,
+            total_files: 3,
+            total_dirs: 1,
+            ..Default::default()
+        };
+        summary.ext.insert(
+            ".rs".to_string(),
+            ExtSummary {
+                files: 2,
+                bytes: 40,
+            },
+        );
+        let json = serde_json::to_string(&summary).unwrap();
+        let back: ListingSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.total_bytes, 42);
+        assert_eq!(back.total_files, 3);
+        assert_eq!(back.total_dirs, 1);
+        assert_eq!(back.ext[".rs"].files, 2);
+        assert_eq!(back.ext[".rs"].bytes, 40);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn entry_serializes_non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let td = tempfile::tempdir().unwrap();
+        let raw = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        let path = td.path().join(raw);
+        if fs::write(&path, b"abc").is_err() {
+            // Some filesystems reject non-UTF-8 names.
+            return;
+        }
+        let entry = make_entry(&path);
+        let value = serde_json::to_value(&entry).unwrap();
+        let name: OsString = serde_json::from_value(value["name"].clone()).unwrap();
+        assert_eq!(name, raw);
+        let path_back: OsString = serde_json::from_value(value["path"].clone()).unwrap();
+        assert_eq!(PathBuf::from(path_back), path);
+        assert_eq!(value["kind"], "file");
+        assert_eq!(value["size"], 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sparse_file_detected() {
+        let td = tempfile::tempdir().unwrap();
+        let sparse = td.path().join("sparse.img");
+        fs::File::create(&sparse)
+            .unwrap()
+            .set_len(16 * 1024 * 1024)
+            .unwrap();
+        let dense = td.path().join("dense.txt");
+        fs::write(&dense, vec![b'x'; 8192]).unwrap();
+
+        assert!(is_sparse(&make_entry(&sparse)));
+        assert!(!is_sparse(&make_entry(&dense)));
+    }
+
+    #[test]
+    fn tree_json_nests_children() {
+        let td = tempfile::tempdir().unwrap();
+        fs::create_dir_all(td.path().join("a/b")).unwrap();
+        fs::write(td.path().join("a/b/deep.txt"), b"x").unwrap();
+        fs::write(td.path().join("a/mid.txt"), b"x").unwrap();
+        fs::write(td.path().join("top.txt"), b"x").unwrap();
+
+        let cli = Cli::parse_from(["lz", "--tree"]);
+        let (entries, _) = collect_listing(td.path(), &cli.options).unwrap();
+        let nested = build_nested_json(&entries);
+
+        assert_eq!(nested.len(), 1);
+        let root = &nested[0];
+        assert_eq!(root.rel_path, ".");
+        let names: Vec<_> = root.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "top.txt"]);
+        let a = &root.children[0];
+        let names: Vec<_> = a.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "mid.txt"]);
+        assert_eq!(a.children[0].children[0].name, "deep.txt");
+    }
+
+    #[test]
+    fn timestamp_precision_truncates() {
+        let time = SystemTime::UNIX_EPOCH + Duration::new(90, 123_456_789);
+        let format = |args: &[&str]| {
+            let cli = Cli::parse_from(["lz"].iter().chain(args));
+            format_timestamp(time, &cli.options, true)
+        };
+        assert_eq!(
+            format(&["--timestamp-precision", "seconds"]),
+            "1970-01-01T00:01:30Z"
+        );
+        assert_eq!(
+            format(&["--timestamp-precision", "millis"]),
+            "1970-01-01T00:01:30.123Z"
+        );
+        assert_eq!(
+            format(&["--timestamp-precision", "nanos"]),
+            "1970-01-01T00:01:30.123456789Z"
+        );
+    }
+
+    #[test]
+    fn parse_mounts_unescapes_paths() {
+        let mounts = parse_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n/dev/sdb1 /mnt/my\\040disk vfat rw 0 0\n",
+        );
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].fs_type, "ext4");
+        assert_eq!(mounts[1].mount_point, PathBuf::from("/mnt/my disk"));
+        assert_eq!(mounts[1].fs_type, "vfat");
+    }
+
+    #[test]
+    fn ssh_target_parses_user_host_and_path() {
+        let target = SshTarget::parse("alice@example.com:/srv/www").unwrap();
+        assert_eq!(target.user, "alice");
+        assert_eq!(target.host, "example.com");
+        assert_eq!(target.path, "/srv/www");
+        assert_eq!(SshTarget::parse("bob@box").unwrap().path, ".");
+        assert_eq!(target.port, 22);
+        let target = SshTarget::parse("bob@box:2222:/var/log").unwrap();
+        assert_eq!((target.port, target.path.as_str()), (2222, "/var/log"));
+        assert_eq!(SshTarget::parse("bob@box:2222").unwrap().port, 2222);
+        assert!(SshTarget::parse("bob@:/tmp").is_err());
+    }
+
+    #[test]
+    fn s3_target_parses_bucket_and_prefix() {
+        let target = S3Target::parse("s3://logs/2024/app").unwrap();
+        assert_eq!(target.bucket, "logs");
+        assert_eq!(target.prefix, "2024/app/");
+        assert_eq!(S3Target::parse("s3://logs").unwrap().prefix, "");
+        assert!(S3Target::parse("logs/2024").is_err());
+        assert!(S3Target::parse("s3:///2024").is_err());
+    }
+
+    #[test]
+    fn yaml_flow_parses_back() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(td.path().join("a: b.txt"), b"xyz").unwrap();
+        let cli = Cli::parse_from(["lz", "--yaml"]);
+        let (entries, _) = collect_listing(td.path(), &cli.options).unwrap();
+        let out = JsonOutput {
+            version: JSON_FORMAT_VERSION,
+            refresh_id: None,
+            root: td.path().display().to_string(),
+            entries: entries.iter().map(|e| e.to_json()).collect(),
+            summary: None,
+            error: None,
+        };
+
+        let flow = output_entries_yaml(&out, true).unwrap();
+        assert!(!flow.contains('\n'));
+        let back: JsonOutput = serde_yaml::from_str(&flow).unwrap();
+        assert_eq!(back.entries.len(), 1);
+        assert_eq!(back.entries[0].name, "a: b.txt");
+        assert_eq!(back.entries[0].size, 3);
+    }
+
+    #[test]
+    fn fuzzy_filter_ranks_by_score() {
+        let td = tempfile::tempdir().unwrap();
+        for name in ["main.rs", "mod_main.rs", "lib.rs"] {
+            fs::write(td.path().join(name), b"x").unwrap();
+        }
+
+        let cli = Cli::parse_from(["lz", "--filter", "main", "--fuzzy"]);
+        let matcher = compile_filter(&cli.options).unwrap();
+        let entries =
+            build_display_entries_for_dir(td.path(), td.path(), &cli.options, matcher.as_ref())
+                .unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(|e| e.entry.name.to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(names, vec!["main.rs", "mod_main.rs"]);
+    }
+
+    #[test]
+    fn sort_dirs_by_subtree_size() {
+        let td = tempfile::tempdir().unwrap();
+        for (dir, bytes) in [("a_small", 10), ("b_big", 5000), ("c_mid", 300)] {
+            fs::create_dir(td.path().join(dir)).unwrap();
+            fs::write(td.path().join(dir).join("data"), vec![0u8; bytes]).unwrap();
+        }
+
+        let cli = Cli::parse_from(["lz", "--sort", "size", "--du", "--sort-dirs-by-size"]);
+        let entries =
+            build_display_entries_for_dir(td.path(), td.path(), &cli.options, None).unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(|e| e.entry.name.to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(names, vec!["b_big", "c_mid", "a_small"]);
+    }
 }